@@ -0,0 +1,184 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+use super::{matrix_error, parse_response, ApiError, HttpRequest, Transport};
+
+/// Preset of the join rules, history visibility and power levels of a new room.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomPreset {
+    PrivateChat,
+    /// Like `PrivateChat`, but invited users get the same power level as the creator.
+    TrustedPrivateChat,
+    PublicChat,
+}
+
+impl FromStr for RoomPreset {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "private_chat" => Ok(RoomPreset::PrivateChat),
+            "trusted_private_chat" => Ok(RoomPreset::TrustedPrivateChat),
+            "public_chat" => Ok(RoomPreset::PublicChat),
+            _ => Err(ApiError::UnknownRoomPreset(s.to_owned())),
+        }
+    }
+}
+
+/// Settings of a room to create.
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct NewRoom {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<RoomPreset>,
+    /// User IDs to invite.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub invite: Vec<String>,
+    /// Local part of the alias to publish, e.g. `alerts` for `#alerts:example.org`.
+    #[serde(rename = "room_alias_name", skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Marks the room as a direct chat for the invited users.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_direct: bool,
+}
+
+impl NewRoom {
+    /// A private direct chat with `user_id`.
+    pub fn direct(user_id: impl Into<String>) -> Self {
+        Self {
+            preset: Some(RoomPreset::TrustedPrivateChat),
+            invite: vec![user_id.into()],
+            is_direct: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_topic(mut self, topic: impl Into<String>) -> Self {
+        self.topic = Some(topic.into());
+        self
+    }
+
+    pub fn with_preset(mut self, preset: RoomPreset) -> Self {
+        self.preset = Some(preset);
+        self
+    }
+
+    pub fn inviting(mut self, user_id: impl Into<String>) -> Self {
+        self.invite.push(user_id.into());
+        self
+    }
+
+    pub fn with_alias(mut self, alias: impl Into<String>) -> Self {
+        self.alias = Some(alias.into());
+        self
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct CreateRoomResponseBody {
+    room_id: String,
+}
+
+/// Creates a room, returning its ID.
+pub async fn create_room(
+    room: &NewRoom,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<String, ApiError> {
+    let create_room_body_json = serde_json::to_string(room)?;
+
+    let create_room_url = config.get_create_room_url();
+
+    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
+
+    let response = transport
+        .send(
+            HttpRequest::post(create_room_url.as_str())
+                .body(create_room_body_json)
+                .bearer_auth(token.as_str()),
+        )
+        .await?;
+
+    if !response.status.is_success() {
+        return Err(matrix_error(response.status, &response.body));
+    }
+
+    let create_room_response: CreateRoomResponseBody =
+        parse_response(&create_room_url, &response.body)?;
+
+    Ok(create_room_response.room_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        Fake,
+    };
+
+    use crate::config::Config;
+
+    use super::{create_room, NewRoom, RoomPreset};
+
+    #[tokio::test]
+    async fn test_create_room() {
+        let mut mock_server = mockito::Server::new();
+
+        let base_url = format!("http://{}", mock_server.host_with_port());
+
+        let config = Config {
+            base_url: base_url.clone(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+
+        let full_create_room_url = config.get_create_room_url();
+        let create_room_url = full_create_room_url
+            .strip_prefix(base_url.as_str())
+            .expect("Base URL missing from create room url");
+
+        let mock_endpoint = mock_server
+            .mock("POST", create_room_url)
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "name": "Alerts",
+                "topic": "Production alerts",
+                "preset": "private_chat",
+                "invite": ["@alice:testmatrix"],
+                "room_alias_name": "alerts",
+            })))
+            .with_status(200)
+            .with_body(r#"{"room_id": "!newroom:testmatrix"}"#)
+            .create();
+
+        let room = NewRoom::default()
+            .named("Alerts")
+            .with_topic("Production alerts")
+            .with_preset(RoomPreset::PrivateChat)
+            .inviting("@alice:testmatrix")
+            .with_alias("alerts");
+
+        let client = reqwest::Client::new();
+
+        let func_result = create_room(&room, &config, &client).await;
+
+        mock_endpoint.assert();
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert_eq!(func_result.unwrap(), "!newroom:testmatrix");
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use reqwest::StatusCode;
+
+use crate::config::Config;
+
+use super::{matrix_error, parse_response, ApiError, HttpRequest, Transport};
+
+/// Direct chat rooms per user ID, as stored in the `m.direct` account data.
+pub type DirectRooms = BTreeMap<String, Vec<String>>;
+
+/// Fetches the `m.direct` account data of the user, empty if it was never set.
+pub async fn get_direct_rooms(
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<DirectRooms, ApiError> {
+    let direct_rooms_url = config.get_direct_rooms_url();
+
+    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
+
+    let response = transport
+        .send(HttpRequest::get(direct_rooms_url.as_str()).bearer_auth(token.as_str()))
+        .await?;
+
+    if response.status == StatusCode::NOT_FOUND {
+        return Ok(DirectRooms::new());
+    }
+    if !response.status.is_success() {
+        return Err(matrix_error(response.status, &response.body));
+    }
+
+    parse_response(&direct_rooms_url, &response.body)
+}
+
+/// Replaces the `m.direct` account data of the user.
+pub async fn set_direct_rooms(
+    direct_rooms: &DirectRooms,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<(), ApiError> {
+    let direct_rooms_body_json = serde_json::to_string(direct_rooms)?;
+
+    let direct_rooms_url = config.get_direct_rooms_url();
+
+    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
+
+    let response = transport
+        .send(
+            HttpRequest::put(direct_rooms_url.as_str())
+                .body(direct_rooms_body_json)
+                .bearer_auth(token.as_str()),
+        )
+        .await?;
+
+    if !response.status.is_success() {
+        return Err(matrix_error(response.status, &response.body));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        Fake,
+    };
+    use reqwest::Method;
+
+    use crate::api::InMemoryTransport;
+    use crate::config::Config;
+
+    use super::{get_direct_rooms, set_direct_rooms, DirectRooms};
+
+    #[tokio::test]
+    async fn test_get_direct_rooms_unset() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: format!("@{}:testmatrix", Username().fake::<String>()),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::GET,
+            config.get_direct_rooms_url(),
+            404,
+            r#"{"errcode": "M_NOT_FOUND", "error": "Account data not found"}"#,
+        );
+
+        let func_result = get_direct_rooms(&config, &transport).await;
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert!(func_result.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_and_get_direct_rooms() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: format!("@{}:testmatrix", Username().fake::<String>()),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let transport = InMemoryTransport::new();
+        transport
+            .respond(Method::PUT, config.get_direct_rooms_url(), 200, "{}")
+            .respond(
+                Method::GET,
+                config.get_direct_rooms_url(),
+                200,
+                r#"{"@alice:testmatrix": ["!dm:testmatrix"]}"#,
+            );
+
+        let direct_rooms = DirectRooms::from([(
+            "@alice:testmatrix".to_owned(),
+            vec!["!dm:testmatrix".to_owned()],
+        )]);
+
+        let set_result = set_direct_rooms(&direct_rooms, &config, &transport).await;
+        assert!(set_result.is_ok(), "{:?}", set_result);
+
+        let get_result = get_direct_rooms(&config, &transport).await;
+        assert!(get_result.is_ok(), "{:?}", get_result);
+        assert_eq!(get_result.unwrap(), direct_rooms);
+
+        let requests = transport.requests();
+        assert_eq!(
+            requests[0].body.as_deref(),
+            Some(r#"{"@alice:testmatrix":["!dm:testmatrix"]}"#)
+        );
+    }
+}
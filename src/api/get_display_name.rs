@@ -0,0 +1,94 @@
+use serde::Deserialize;
+
+use crate::config::Config;
+
+use super::{matrix_error, parse_response, ApiError, HttpRequest, Transport};
+
+#[derive(Deserialize, Debug)]
+struct ProfileResponseBody {
+    displayname: Option<String>,
+}
+
+/// Looks up the display name of any user, `None` if they have not set one.
+pub async fn get_display_name(
+    user_id: &str,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<Option<String>, ApiError> {
+    let profile_url = config.get_user_profile_url(user_id);
+
+    let mut request = HttpRequest::get(profile_url.as_str());
+    if let Some(token) = &config.token {
+        request = request.bearer_auth(token.as_str());
+    }
+    let response = transport.send(request).await?;
+
+    if !response.status.is_success() {
+        return Err(matrix_error(response.status, &response.body));
+    }
+
+    let profile_response: ProfileResponseBody = parse_response(&profile_url, &response.body)?;
+
+    Ok(profile_response.displayname)
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        faker::name::en::Name,
+        Fake,
+    };
+
+    use crate::config::Config;
+
+    use super::get_display_name;
+
+    #[tokio::test]
+    async fn test_get_display_name() {
+        let mut mock_server = mockito::Server::new();
+
+        let base_url = format!("http://{}", mock_server.host_with_port());
+
+        let config = Config {
+            base_url: base_url.clone(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+
+        let user_id = "@alice:testmatrix";
+        let display_name: String = Name().fake();
+
+        let full_profile_url = config.get_user_profile_url(user_id);
+        let profile_url = full_profile_url
+            .strip_prefix(base_url.as_str())
+            .expect("Base URL missing from profile url");
+        let profile_response_body = format!(
+            r#"
+{{
+    "displayname": "{}",
+    "avatar_url": null
+}}
+"#,
+            display_name
+        );
+
+        let mock_endpoint = mock_server
+            .mock("GET", profile_url)
+            .with_status(200)
+            .with_body(profile_response_body.as_str())
+            .create();
+
+        let client = reqwest::Client::new();
+
+        let func_result = get_display_name(user_id, &config, &client).await;
+
+        mock_endpoint.assert();
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert_eq!(func_result.unwrap(), Some(display_name));
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+use super::{matrix_error, parse_response, ApiError, HttpRequest, Transport};
+
+/// A pending invite of the user into a room.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Invite {
+    pub room_id: String,
+    /// User who sent the invite, if the homeserver shared it.
+    pub inviter: Option<String>,
+    pub room_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SyncResponseBody {
+    #[serde(default)]
+    rooms: SyncRooms,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct SyncRooms {
+    #[serde(default)]
+    invite: BTreeMap<String, InvitedRoom>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct InvitedRoom {
+    #[serde(default)]
+    invite_state: InviteState,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct InviteState {
+    #[serde(default)]
+    events: Vec<StrippedStateEvent>,
+}
+
+#[derive(Deserialize, Debug)]
+struct StrippedStateEvent {
+    #[serde(rename = "type")]
+    event_type: String,
+    state_key: String,
+    sender: String,
+    content: serde_json::Value,
+}
+
+/// Lists the rooms the user is invited to, using a `/sync` that skips timelines and presence.
+pub async fn get_invites(
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<Vec<Invite>, ApiError> {
+    let sync_url = config.get_invites_sync_url();
+
+    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
+
+    let response = transport
+        .send(HttpRequest::get(sync_url.as_str()).bearer_auth(token.as_str()))
+        .await?;
+
+    if !response.status.is_success() {
+        return Err(matrix_error(response.status, &response.body));
+    }
+
+    let sync_response: SyncResponseBody = parse_response(&sync_url, &response.body)?;
+
+    let invites = sync_response
+        .rooms
+        .invite
+        .into_iter()
+        .map(|(room_id, room)| {
+            let events = room.invite_state.events;
+            let inviter = events
+                .iter()
+                .find(|event| {
+                    event.event_type == "m.room.member"
+                        && event.state_key == config.full_username
+                        && event.content["membership"] == "invite"
+                })
+                .map(|event| event.sender.clone());
+            let room_name = events
+                .iter()
+                .find(|event| event.event_type == "m.room.name")
+                .and_then(|event| event.content["name"].as_str())
+                .map(str::to_owned);
+            Invite {
+                room_id,
+                inviter,
+                room_name,
+            }
+        })
+        .collect();
+
+    Ok(invites)
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        Fake,
+    };
+    use reqwest::Method;
+
+    use crate::api::InMemoryTransport;
+    use crate::config::Config;
+
+    use super::{get_invites, Invite};
+
+    #[tokio::test]
+    async fn test_get_invites() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: "@bot:testmatrix".to_owned(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let sync_response_body = r#"
+{
+    "next_batch": "s1",
+    "rooms": {
+        "invite": {
+            "!alerts:testmatrix": {
+                "invite_state": {
+                    "events": [
+                        {
+                            "type": "m.room.name",
+                            "state_key": "",
+                            "sender": "@admin:testmatrix",
+                            "content": { "name": "Alerts" }
+                        },
+                        {
+                            "type": "m.room.member",
+                            "state_key": "@bot:testmatrix",
+                            "sender": "@admin:testmatrix",
+                            "content": { "membership": "invite" }
+                        }
+                    ]
+                }
+            },
+            "!bare:testmatrix": {}
+        }
+    }
+}
+"#;
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::GET,
+            config.get_invites_sync_url(),
+            200,
+            sync_response_body,
+        );
+
+        let func_result = get_invites(&config, &transport).await;
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert_eq!(
+            func_result.unwrap(),
+            vec![
+                Invite {
+                    room_id: "!alerts:testmatrix".to_owned(),
+                    inviter: Some("@admin:testmatrix".to_owned()),
+                    room_name: Some("Alerts".to_owned()),
+                },
+                Invite {
+                    room_id: "!bare:testmatrix".to_owned(),
+                    inviter: None,
+                    room_name: None,
+                },
+            ]
+        );
+    }
+}
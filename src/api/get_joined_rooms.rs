@@ -0,0 +1,33 @@
+use serde::Deserialize;
+
+use crate::config::Config;
+
+use super::{matrix_error, parse_response, ApiError, HttpRequest, Transport};
+
+#[derive(Deserialize, Debug)]
+struct JoinedRoomsResponseBody {
+    joined_rooms: Vec<String>,
+}
+
+/// Lists the IDs of all rooms the user has joined.
+pub async fn get_joined_rooms(
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<Vec<String>, ApiError> {
+    let joined_rooms_url = config.get_joined_rooms_url();
+
+    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
+
+    let response = transport
+        .send(HttpRequest::get(joined_rooms_url.as_str()).bearer_auth(token.as_str()))
+        .await?;
+
+    if !response.status.is_success() {
+        return Err(matrix_error(response.status, &response.body));
+    }
+
+    let joined_rooms_response: JoinedRoomsResponseBody =
+        parse_response(&joined_rooms_url, &response.body)?;
+
+    Ok(joined_rooms_response.joined_rooms)
+}
@@ -1,26 +1,50 @@
-use reqwest::Client;
+use reqwest::StatusCode;
 
 use crate::config::Config;
 
-use super::ApiError;
-
-pub async fn join_room(room: &str, config: &Config, client: &Client) -> Result<(), ApiError> {
-    match &config.token {
-        Some(token) => {
-            let join_url = config.get_join_room_url(room);
-            let join_response = client
-                .post(&join_url)
-                .bearer_auth(token.as_str())
-                .send()
-                .await?;
-
-            if join_response.status().is_success() {
-                return Ok(());
-            }
-        }
-        None => {}
+use super::{matrix_error, ApiError, HttpRequest, Transport};
+
+/// Joins a room by ID or alias, over federation through the `via` servers if the
+/// homeserver does not know the room yet. The server of `room` itself is always tried.
+pub async fn join_room(
+    room: &str,
+    via: &[String],
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<(), ApiError> {
+    let join_url = config.get_join_room_url(room, via);
+
+    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
+
+    let response = transport
+        .send(HttpRequest::post(join_url).bearer_auth(token.as_str()))
+        .await?;
+
+    if response.status.is_success() {
+        return Ok(());
+    }
+
+    match matrix_error(response.status, &response.body) {
+        ApiError::Forbidden { error_message } => Err(ApiError::NotInvited {
+            room: room.to_owned(),
+            error_message,
+        }),
+        ApiError::MatrixApiError {
+            status_code,
+            error_message,
+        } if status_code == StatusCode::NOT_FOUND => Err(ApiError::RoomNotFound {
+            room: room.to_owned(),
+            error_message,
+        }),
+        ApiError::MatrixApiError {
+            status_code,
+            error_message,
+        } if status_code.is_server_error() => Err(ApiError::RoomUnreachable {
+            room: room.to_owned(),
+            error_message,
+        }),
+        e => Err(e),
     }
-    Err(ApiError::JoinRoomFailed(403))
 }
 
 #[cfg(test)]
@@ -31,6 +55,10 @@ mod tests {
         Fake,
     };
 
+    use matches::assert_matches;
+    use reqwest::Method;
+
+    use crate::api::{ApiError, InMemoryTransport};
     use crate::config::Config;
 
     use super::join_room;
@@ -47,33 +75,113 @@ mod tests {
             full_username: Username().fake(),
             password: None,
             token: Some(Password(16..24).fake()),
+            ..Default::default()
         };
 
         let room: String = Word().fake();
 
-        let full_join_url = config.get_join_room_url(room.as_str());
+        let full_join_url = config.get_join_room_url(room.as_str(), &[]);
         let join_url = full_join_url
             .strip_prefix(base_url.as_str())
             .expect("Base URL missing from profile url");
-        let join_response_body = format!(
-            r#"
-{{
-}}
-"#,
-        );
+        let join_response_body = r#"
+{
+}
+"#;
 
         let mock_endpoint = mock_server
             .mock("POST", join_url)
             .with_status(200)
-            .with_body(join_response_body.as_str())
+            .with_body(join_response_body)
             .create();
 
         let client = reqwest::Client::new();
 
-        let func_result = join_room(room.as_str(), &config, &client).await;
+        let func_result = join_room(room.as_str(), &[], &config, &client).await;
 
         mock_endpoint.assert();
 
         assert!(func_result.is_ok(), "{:?}", func_result);
     }
+
+    #[tokio::test]
+    async fn test_join_room_not_invited() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let room = "!private:remote.example";
+
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::POST,
+            config.get_join_room_url(room, &[]),
+            403,
+            r#"{"errcode": "M_FORBIDDEN", "error": "You are not invited to this room."}"#,
+        );
+
+        let func_result = join_room(room, &[], &config, &transport).await;
+
+        assert_matches!(func_result, Err(ApiError::NotInvited { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_join_room_unreachable() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let room = "#alerts:offline.example";
+        let via = vec!["relay.example".to_owned()];
+
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::POST,
+            config.get_join_room_url(room, &via),
+            502,
+            r#"{"errcode": "M_UNKNOWN", "error": "Failed to make_join via any server"}"#,
+        );
+
+        let func_result = join_room(room, &via, &config, &transport).await;
+
+        assert_matches!(func_result, Err(ref e @ ApiError::RoomUnreachable { .. }) if e.is_retryable());
+        assert_eq!(
+            transport.requests()[0].url,
+            "http://localhost/_matrix/client/r0/join/%23alerts%3Aoffline.example\
+?server_name=relay.example&server_name=offline.example"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_join_room_not_found() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let room = "#typo:testmatrix";
+
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::POST,
+            config.get_join_room_url(room, &[]),
+            404,
+            r#"{"errcode": "M_NOT_FOUND", "error": "Room alias #typo:testmatrix not found"}"#,
+        );
+
+        let func_result = join_room(room, &[], &config, &transport).await;
+
+        assert_matches!(func_result, Err(ref e @ ApiError::RoomNotFound { .. }) if !e.is_retryable());
+    }
 }
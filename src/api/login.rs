@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::config::Config;
 
-use super::ApiError;
+use super::{parse_response, ApiError, HttpRequest, Transport};
 
 #[derive(Serialize, Debug)]
 struct LoginRequestBody {
@@ -29,28 +29,25 @@ struct LoginResponseBody {
     device_id: String,
 }
 
-pub async fn login(config: &Config, client: &reqwest::Client) -> Result<String, ApiError> {
+pub async fn login(config: &Config, transport: &dyn Transport) -> Result<String, ApiError> {
     let user = &config.local_username;
     let password = config.password.clone().ok_or(ApiError::MissingPassword)?;
 
     let login_url = config.get_login_url();
     let login_send_body_obj = LoginRequestBody::new(user.as_str(), password.as_str());
-    let login_send_body_json =
-        serde_json::to_string(&login_send_body_obj).expect("Bad json request");
+    let login_send_body_json = serde_json::to_string(&login_send_body_obj)?;
 
-    let login_response = client
-        .post(login_url)
-        .body(login_send_body_json)
-        .send()
+    let login_response = transport
+        .send(HttpRequest::post(login_url.as_str()).body(login_send_body_json))
         .await?;
 
-    if login_response.status().is_success() {
-        let login_response_json = login_response.text().await?;
-        let login_response_obj = serde_json::from_str::<LoginResponseBody>(&login_response_json)?;
+    if login_response.status.is_success() {
+        let login_response_obj: LoginResponseBody =
+            parse_response(&login_url, &login_response.body)?;
         return Ok(login_response_obj.access_token);
     }
 
-    Err(ApiError::LoginFailed(login_response.status().as_u16()))
+    Err(ApiError::LoginFailed(login_response.status.as_u16()))
 }
 
 #[cfg(test)]
@@ -76,6 +73,7 @@ mod tests {
             full_username: Username().fake(),
             password: Some(Password(16..24).fake()),
             token: None,
+            ..Default::default()
         };
 
         let token: String = Password(42..43).fake();
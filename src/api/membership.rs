@@ -0,0 +1,196 @@
+use serde::Serialize;
+
+use crate::config::Config;
+
+use super::{matrix_error, ApiError, HttpRequest, Transport};
+
+#[derive(Serialize, Debug)]
+struct MembershipRequestBody<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+}
+
+/// Invites a user into a room.
+pub async fn invite(
+    room: &str,
+    user_id: &str,
+    reason: Option<&str>,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<(), ApiError> {
+    let body = MembershipRequestBody {
+        user_id: Some(user_id),
+        reason,
+    };
+    post_membership(config.get_invite_url(room), &body, config, transport).await
+}
+
+/// Removes a user from a room, they may join again if invited or the room is public.
+pub async fn kick(
+    room: &str,
+    user_id: &str,
+    reason: Option<&str>,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<(), ApiError> {
+    let body = MembershipRequestBody {
+        user_id: Some(user_id),
+        reason,
+    };
+    post_membership(config.get_kick_url(room), &body, config, transport).await
+}
+
+/// Leaves a room, also rejecting a pending invite to it.
+pub async fn leave_room(
+    room: &str,
+    reason: Option<&str>,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<(), ApiError> {
+    let body = MembershipRequestBody {
+        user_id: None,
+        reason,
+    };
+    post_membership(config.get_leave_room_url(room), &body, config, transport).await
+}
+
+/// Forgets a room that was left, removing it from the room list of the user.
+pub async fn forget_room(
+    room: &str,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<(), ApiError> {
+    let body = MembershipRequestBody {
+        user_id: None,
+        reason: None,
+    };
+    post_membership(config.get_forget_room_url(room), &body, config, transport).await
+}
+
+async fn post_membership(
+    url: String,
+    body: &MembershipRequestBody<'_>,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<(), ApiError> {
+    let membership_body_json = serde_json::to_string(body)?;
+
+    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
+
+    let response = transport
+        .send(
+            HttpRequest::post(url)
+                .body(membership_body_json)
+                .bearer_auth(token.as_str()),
+        )
+        .await?;
+
+    if !response.status.is_success() {
+        return Err(matrix_error(response.status, &response.body));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        faker::lorem::en::{Sentence, Word},
+        Fake,
+    };
+    use matches::assert_matches;
+    use reqwest::Method;
+
+    use crate::api::{ApiError, InMemoryTransport};
+    use crate::config::Config;
+
+    use super::{forget_room, invite, kick, leave_room};
+
+    #[tokio::test]
+    async fn test_invite() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let room: String = Word().fake();
+        let transport = InMemoryTransport::new();
+        transport.respond(Method::POST, config.get_invite_url(&room), 200, "{}");
+
+        let func_result = invite(&room, "@alice:testmatrix", None, &config, &transport).await;
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert_eq!(
+            transport.requests()[0].body.as_deref(),
+            Some(r#"{"user_id":"@alice:testmatrix"}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kick_forbidden() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let room: String = Word().fake();
+        let reason: String = Sentence(1..2).fake();
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::POST,
+            config.get_kick_url(&room),
+            403,
+            r#"{"errcode": "M_FORBIDDEN", "error": "You cannot kick user @alice:testmatrix."}"#,
+        );
+
+        let func_result = kick(
+            &room,
+            "@alice:testmatrix",
+            Some(&reason),
+            &config,
+            &transport,
+        )
+        .await;
+
+        assert_matches!(func_result, Err(ApiError::Forbidden { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_leave_and_forget_room() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let room: String = Word().fake();
+        let transport = InMemoryTransport::new();
+        transport
+            .respond(Method::POST, config.get_leave_room_url(&room), 200, "{}")
+            .respond(Method::POST, config.get_forget_room_url(&room), 200, "{}");
+
+        let leave_result = leave_room(&room, Some("Room retired"), &config, &transport).await;
+        assert!(leave_result.is_ok(), "{:?}", leave_result);
+
+        let forget_result = forget_room(&room, &config, &transport).await;
+        assert!(forget_result.is_ok(), "{:?}", forget_result);
+
+        let requests = transport.requests();
+        assert_eq!(
+            requests[0].body.as_deref(),
+            Some(r#"{"reason":"Room retired"}"#)
+        );
+        assert_eq!(requests[1].body.as_deref(), Some("{}"));
+    }
+}
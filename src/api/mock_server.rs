@@ -1,4 +1,93 @@
-use mockito::{Mock, Server, ServerGuard};
+use mockito::{Matcher, Mock, Server, ServerGuard};
+
+/// Placeholder for the transaction ID when building urls passed to `path_matcher`.
+pub const ANY_TXN_ID: &str = "any-txn-id";
+/// Placeholder for the event ID when building urls passed to `path_matcher`.
+pub const ANY_EVENT_ID: &str = "any-event-id";
+/// Custom event type accepted by `MockMatrix::send_event_endpoint`.
+pub const TEST_EVENT_TYPE: &str = "com.example.test";
+
+/// Matches the path of a url built by the config helpers,
+/// accepting any ID where `ANY_TXN_ID` or `ANY_EVENT_ID` was used.
+pub fn path_matcher(base_url: &str, url: &str) -> Matcher {
+    let stripped_url = url
+        .strip_prefix(base_url)
+        .expect("Base URL missing from built url");
+
+    let pattern = [ANY_TXN_ID, ANY_EVENT_ID]
+        .iter()
+        .fold(escape_regex(stripped_url), |pattern, placeholder| {
+            pattern.replace(escape_regex(placeholder).as_str(), "[^/]+")
+        });
+    Matcher::Regex(format!("^{}$", pattern))
+}
+
+fn escape_regex(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if "\\.+*?()|[]{}^$#&-~".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+const EMPTY_RESPONSE_BODY: &str = r#"
+{
+}
+"#;
+
+const LOGIN_RESPONSE_BODY: &str = r#"
+{
+    "access_token": "testtoken",
+    "user_id": "testuser",
+    "home_server": "testmatrix",
+    "device_id": "testingdevice"
+}
+"#;
+
+const SEND_MESSAGE_RESPONSE_BODY: &str = r#"
+{
+    "event_id": "$testevent:testmatrix"
+}
+"#;
+
+const REDACT_RESPONSE_BODY: &str = r#"
+{
+    "event_id": "$testredaction:testmatrix"
+}
+"#;
+
+const REACTION_RESPONSE_BODY: &str = r#"
+{
+    "event_id": "$testreaction:testmatrix"
+}
+"#;
+
+const SEND_EVENT_RESPONSE_BODY: &str = r#"
+{
+    "event_id": "$testcustomevent:testmatrix"
+}
+"#;
+
+const TOPIC_RESPONSE_BODY: &str = r#"
+{
+    "event_id": "$testtopic:testmatrix"
+}
+"#;
+
+const NOT_FOUND_RESPONSE_BODY: &str = r#"
+{
+    "errcode": "M_NOT_FOUND",
+    "error": "Not found"
+}
+"#;
+
+const MALFORMED_RESPONSE_BODY: &str = r#"
+{
+    "this is": "not valid json"
+"#;
 
 pub struct MockMatrix {
     pub server: ServerGuard,
@@ -6,38 +95,150 @@ pub struct MockMatrix {
     pub profile_endpoint: Mock,
     pub login_endpoint: Mock,
 
-    pub room_members_endpoint: Mock,
+    pub joined_rooms_endpoint: Mock,
     pub join_room_endpoint: Mock,
+    pub invite_endpoint: Mock,
+    pub kick_endpoint: Mock,
+    pub leave_room_endpoint: Mock,
+    pub forget_room_endpoint: Mock,
 
     pub send_message_endpoint: Mock,
+    pub redact_endpoint: Mock,
+    pub reaction_endpoint: Mock,
+    pub send_event_endpoint: Mock,
+    pub topic_endpoint: Mock,
+
+    pub invites_sync_endpoint: Mock,
+    pub create_room_endpoint: Mock,
+    pub get_direct_rooms_endpoint: Mock,
+    pub set_direct_rooms_endpoint: Mock,
 }
 
 impl MockMatrix {
     pub fn new(room: &str, full_username: &str) -> Self {
+        MockMatrix::build(
+            room,
+            full_username,
+            LOGIN_RESPONSE_BODY,
+            SEND_MESSAGE_RESPONSE_BODY,
+        )
+    }
+
+    /// Builds a server whose login and send message endpoints succeed
+    /// but respond with bodies that do not match the Matrix spec.
+    pub fn new_malformed(room: &str, full_username: &str) -> Self {
+        MockMatrix::build(
+            room,
+            full_username,
+            MALFORMED_RESPONSE_BODY,
+            MALFORMED_RESPONSE_BODY,
+        )
+    }
+
+    fn build(
+        room: &str,
+        full_username: &str,
+        login_response_body: &str,
+        send_message_response_body: &str,
+    ) -> Self {
+        let joined_rooms_response_body = format!(
+            r#"
+{{
+    "joined_rooms": ["{}"]
+}}
+"#,
+            room
+        );
+
         let mut server = Server::new();
         let base_url = format!("http://{}", server.host_with_port());
 
         let profile_endpoint =
             MockMatrix::generate_profile_endpoint(&mut server, base_url.as_str(), full_username);
-        let login_endpoint = MockMatrix::generate_login_endpoint(&mut server, base_url.as_str());
-        let room_members_endpoint = MockMatrix::generate_room_members_endpoint(
+        let login_endpoint = MockMatrix::generate_login_endpoint(
             &mut server,
             base_url.as_str(),
-            room,
-            full_username,
+            login_response_body,
+        );
+        let joined_rooms_endpoint = MockMatrix::generate_joined_rooms_endpoint(
+            &mut server,
+            base_url.as_str(),
+            joined_rooms_response_body.as_str(),
         );
         let join_room_endpoint =
             MockMatrix::generate_join_room_endpoint(&mut server, base_url.as_str(), room);
-        let send_message_endpoint =
-            MockMatrix::generate_send_message_endpoint(&mut server, base_url.as_str(), room);
+        let invite_endpoint = MockMatrix::generate_room_action_endpoint(
+            &mut server,
+            base_url.as_str(),
+            crate::config::build_invite_url(base_url.as_str(), room),
+        );
+        let kick_endpoint = MockMatrix::generate_room_action_endpoint(
+            &mut server,
+            base_url.as_str(),
+            crate::config::build_kick_url(base_url.as_str(), room),
+        );
+        let leave_room_endpoint = MockMatrix::generate_room_action_endpoint(
+            &mut server,
+            base_url.as_str(),
+            crate::config::build_leave_room_url(base_url.as_str(), room),
+        );
+        let forget_room_endpoint = MockMatrix::generate_room_action_endpoint(
+            &mut server,
+            base_url.as_str(),
+            crate::config::build_forget_room_url(base_url.as_str(), room),
+        );
+        let send_message_endpoint = MockMatrix::generate_send_message_endpoint(
+            &mut server,
+            base_url.as_str(),
+            room,
+            send_message_response_body,
+        );
+        let redact_endpoint =
+            MockMatrix::generate_redact_endpoint(&mut server, base_url.as_str(), room);
+        let reaction_endpoint =
+            MockMatrix::generate_reaction_endpoint(&mut server, base_url.as_str(), room);
+        let send_event_endpoint =
+            MockMatrix::generate_send_event_endpoint(&mut server, base_url.as_str(), room);
+        let topic_endpoint =
+            MockMatrix::generate_topic_endpoint(&mut server, base_url.as_str(), room);
+        let invites_sync_endpoint = MockMatrix::generate_invites_sync_endpoint(
+            &mut server,
+            base_url.as_str(),
+            room,
+            full_username,
+        );
+        let create_room_endpoint =
+            MockMatrix::generate_create_room_endpoint(&mut server, base_url.as_str(), room);
+        let get_direct_rooms_endpoint = MockMatrix::generate_get_direct_rooms_endpoint(
+            &mut server,
+            base_url.as_str(),
+            full_username,
+        );
+        let set_direct_rooms_endpoint = MockMatrix::generate_set_direct_rooms_endpoint(
+            &mut server,
+            base_url.as_str(),
+            full_username,
+        );
 
         Self {
             server,
             profile_endpoint,
             login_endpoint,
-            room_members_endpoint,
+            joined_rooms_endpoint,
             join_room_endpoint,
+            invite_endpoint,
+            kick_endpoint,
+            leave_room_endpoint,
+            forget_room_endpoint,
             send_message_endpoint,
+            redact_endpoint,
+            reaction_endpoint,
+            send_event_endpoint,
+            topic_endpoint,
+            invites_sync_endpoint,
+            create_room_endpoint,
+            get_direct_rooms_endpoint,
+            set_direct_rooms_endpoint,
         }
     }
 
@@ -51,117 +252,218 @@ impl MockMatrix {
             .strip_prefix(base_url)
             .expect("Base URL missing from built url");
 
-        let response_body = format!(
-            r#"
-{{
-}}
-"#,
-        );
-
         server
             .mock("GET", stripped_url)
             .with_status(200)
-            .with_body(response_body.as_str())
+            .with_body(EMPTY_RESPONSE_BODY)
             .create()
     }
 
-    fn generate_login_endpoint(server: &mut ServerGuard, base_url: &str) -> Mock {
+    fn generate_login_endpoint(
+        server: &mut ServerGuard,
+        base_url: &str,
+        response_body: &str,
+    ) -> Mock {
         let url = crate::config::build_login_url(base_url);
         let stripped_url = url
             .strip_prefix(base_url)
             .expect("Base URL missing from built url");
 
-        let response_body = format!(
-            r#"
-{{
-    "access_token": "testtoken",
-    "user_id": "testuser",
-    "home_server": "testmatrix",
-    "device_id": "testingdevice"
-}}
-"#,
-        );
-
         server
             .mock("POST", stripped_url)
             .with_status(200)
-            .with_body(response_body.as_str())
+            .with_body(response_body)
             .create()
     }
 
-    fn generate_room_members_endpoint(
+    fn generate_joined_rooms_endpoint(
         server: &mut ServerGuard,
         base_url: &str,
-        room: &str,
-        full_username: &str,
+        response_body: &str,
     ) -> Mock {
-        let url = crate::config::build_room_members_url(base_url, room);
+        let url = crate::config::build_joined_rooms_url(base_url);
         let stripped_url = url
             .strip_prefix(base_url)
             .expect("Base URL missing from built url");
 
-        let response_body = format!(
-            r#"
-{{
-    "joined": {{
-        "{}": {{
-            "display_name": "Test",
-            "avatar_url": ""
-        }}
-    }}
-}}
-"#,
-            full_username
-        );
-
         server
             .mock("GET", stripped_url)
             .with_status(200)
-            .with_body(response_body.as_str())
+            .with_body(response_body)
             .create()
     }
 
+    /// Accepts joins of `room` through any servers.
     fn generate_join_room_endpoint(server: &mut ServerGuard, base_url: &str, room: &str) -> Mock {
-        let url = crate::config::build_join_room_url(base_url, room);
-        let stripped_url = url
+        let url = crate::config::build_join_room_url(base_url, room, &[]);
+        let stripped_path = url
             .strip_prefix(base_url)
+            .and_then(|stripped_url| stripped_url.split('?').next())
             .expect("Base URL missing from built url");
+        let path_and_query_matcher =
+            Matcher::Regex(format!(r"^{}\?server_name=", escape_regex(stripped_path)));
 
-        let response_body = format!(
-            r#"
-{{
-}}
-"#,
-        );
+        server
+            .mock("POST", path_and_query_matcher)
+            .with_status(200)
+            .with_body(EMPTY_RESPONSE_BODY)
+            .create()
+    }
+
+    /// Accepts a `POST` to a membership url that responds with an empty object on success.
+    fn generate_room_action_endpoint(
+        server: &mut ServerGuard,
+        base_url: &str,
+        url: String,
+    ) -> Mock {
+        let stripped_url = url
+            .strip_prefix(base_url)
+            .expect("Base URL missing from built url");
 
         server
             .mock("POST", stripped_url)
             .with_status(200)
-            .with_body(response_body.as_str())
+            .with_body(EMPTY_RESPONSE_BODY)
             .create()
     }
 
     fn generate_send_message_endpoint(
         server: &mut ServerGuard,
         base_url: &str,
+        room: &str,
+        response_body: &str,
+    ) -> Mock {
+        let url = crate::config::build_send_message_url(base_url, room, ANY_TXN_ID);
+
+        server
+            .mock("PUT", path_matcher(base_url, url.as_str()))
+            .with_status(200)
+            .with_body(response_body)
+            .create()
+    }
+
+    fn generate_redact_endpoint(server: &mut ServerGuard, base_url: &str, room: &str) -> Mock {
+        let url = crate::config::build_redact_url(base_url, room, ANY_EVENT_ID, ANY_TXN_ID);
+
+        server
+            .mock("PUT", path_matcher(base_url, url.as_str()))
+            .with_status(200)
+            .with_body(REDACT_RESPONSE_BODY)
+            .create()
+    }
+
+    fn generate_reaction_endpoint(server: &mut ServerGuard, base_url: &str, room: &str) -> Mock {
+        let url = crate::config::build_send_event_url(base_url, room, "m.reaction", ANY_TXN_ID);
+
+        server
+            .mock("PUT", path_matcher(base_url, url.as_str()))
+            .with_status(200)
+            .with_body(REACTION_RESPONSE_BODY)
+            .create()
+    }
+
+    fn generate_send_event_endpoint(server: &mut ServerGuard, base_url: &str, room: &str) -> Mock {
+        let url = crate::config::build_send_event_url(base_url, room, TEST_EVENT_TYPE, ANY_TXN_ID);
+
+        server
+            .mock("PUT", path_matcher(base_url, url.as_str()))
+            .with_status(200)
+            .with_body(SEND_EVENT_RESPONSE_BODY)
+            .create()
+    }
+
+    fn generate_topic_endpoint(server: &mut ServerGuard, base_url: &str, room: &str) -> Mock {
+        let url = crate::config::build_room_state_url(base_url, room, "m.room.topic", "");
+
+        server
+            .mock("PUT", path_matcher(base_url, url.as_str()))
+            .with_status(200)
+            .with_body(TOPIC_RESPONSE_BODY)
+            .create()
+    }
+
+    /// Responds with a pending invite into `room` from `@admin:testmatrix`.
+    fn generate_invites_sync_endpoint(
+        server: &mut ServerGuard,
+        base_url: &str,
+        room: &str,
         full_username: &str,
     ) -> Mock {
-        let url = crate::config::build_send_message_url(base_url, full_username);
+        let url = crate::config::build_invites_sync_url(base_url);
         let stripped_url = url
             .strip_prefix(base_url)
             .expect("Base URL missing from built url");
+        let response_body = serde_json::json!({
+            "next_batch": "s1",
+            "rooms": {
+                "invite": {
+                    room: {
+                        "invite_state": {
+                            "events": [{
+                                "type": "m.room.member",
+                                "state_key": full_username,
+                                "sender": "@admin:testmatrix",
+                                "content": { "membership": "invite" },
+                            }],
+                        },
+                    },
+                },
+            },
+        });
 
-        let response_body = format!(
-            r#"
-{{
-}}
-"#,
-        );
+        server
+            .mock("GET", stripped_url)
+            .with_status(200)
+            .with_body(response_body.to_string())
+            .create()
+    }
+
+    /// Creating any room yields `room`, as if it had just been created.
+    fn generate_create_room_endpoint(server: &mut ServerGuard, base_url: &str, room: &str) -> Mock {
+        let url = crate::config::build_create_room_url(base_url);
+        let stripped_url = url
+            .strip_prefix(base_url)
+            .expect("Base URL missing from built url");
 
         server
             .mock("POST", stripped_url)
             .with_status(200)
-            .with_body(response_body.as_str())
+            .with_body(format!(r#"{{"room_id": "{}"}}"#, room))
+            .create()
+    }
+
+    /// Responds as if no direct chats were ever recorded.
+    fn generate_get_direct_rooms_endpoint(
+        server: &mut ServerGuard,
+        base_url: &str,
+        full_username: &str,
+    ) -> Mock {
+        let url = crate::config::build_account_data_url(base_url, full_username, "m.direct");
+        let stripped_url = url
+            .strip_prefix(base_url)
+            .expect("Base URL missing from built url");
+
+        server
+            .mock("GET", stripped_url)
+            .with_status(404)
+            .with_body(NOT_FOUND_RESPONSE_BODY)
+            .create()
+    }
+
+    fn generate_set_direct_rooms_endpoint(
+        server: &mut ServerGuard,
+        base_url: &str,
+        full_username: &str,
+    ) -> Mock {
+        let url = crate::config::build_account_data_url(base_url, full_username, "m.direct");
+        let stripped_url = url
+            .strip_prefix(base_url)
+            .expect("Base URL missing from built url");
+
+        server
+            .mock("PUT", stripped_url)
+            .with_status(200)
+            .with_body(EMPTY_RESPONSE_BODY)
             .create()
     }
 }
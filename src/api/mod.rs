@@ -1,15 +1,48 @@
+pub mod create_room;
+pub use create_room::{create_room, NewRoom, RoomPreset};
+pub mod direct_rooms;
+pub use direct_rooms::{get_direct_rooms, set_direct_rooms, DirectRooms};
+pub mod get_display_name;
+pub use get_display_name::get_display_name;
+pub mod get_invites;
+pub use get_invites::{get_invites, Invite};
+pub mod get_joined_rooms;
+pub use get_joined_rooms::get_joined_rooms;
 pub mod join_room;
 pub use join_room::join_room;
 pub mod login;
 pub use login::login;
+pub mod membership;
+pub use membership::{forget_room, invite, kick, leave_room};
+pub mod react;
+pub use react::react;
+pub mod redact;
+pub use redact::redact;
+pub mod resolve_room_alias;
+pub use resolve_room_alias::resolve_room_alias;
+pub mod send_event;
+pub use send_event::{send_event, MESSAGE_EVENT_TYPE};
 pub mod send_message;
+#[cfg(feature = "server")]
+pub(crate) use send_message::escape_html;
 pub use send_message::send_message;
+pub use send_message::{Mention, Message, MessageType, SentEvent};
+pub mod set_state;
+pub use set_state::{set_state, StateEvent};
 pub mod verify_in_room;
 pub use verify_in_room::verify_in_room;
 pub mod verify_token;
 pub use verify_token::verify_token;
+#[cfg(any(test, feature = "test-util"))]
 pub mod mock_server;
+pub mod transport;
+pub use transport::{HttpRequest, HttpResponse, InMemoryTransport, Transport};
 
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use thiserror::Error;
 
@@ -29,23 +62,149 @@ pub enum ApiError {
     MissingToken,
     #[error("Login failed with status: {0}")]
     LoginFailed(u16),
-    #[error("Join room failed with status: {0}")]
-    JoinRoomFailed(u16),
-    #[error("HTTP Error")]
+    #[error("Cannot join {room}, the user is not invited or banned: {error_message}")]
+    NotInvited { room: String, error_message: String },
+    #[error(
+        "Cannot join {room}, no server in the room could be reached, try --via: {error_message}"
+    )]
+    RoomUnreachable { room: String, error_message: String },
+    #[error("The access token is no longer valid: {error_message}")]
+    UnknownToken { error_message: String },
+    #[error("Room alias {0} does not exist")]
+    UnknownRoomAlias(String),
+    #[error("Cannot join {room}, the room is unknown, check the ID or try --via: {error_message}")]
+    RoomNotFound { room: String, error_message: String },
+    #[error("HTTP request to {url} failed: {source}")]
     HttpError { source: reqwest::Error, url: String },
-    #[error("Matrix API Error")]
+    #[error("HTTP transport to {url} failed: {source}")]
+    Transport {
+        url: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Malformed response from {url}: {reason}")]
+    MalformedResponse { url: String, reason: String },
+    #[error("Rate limited by the homeserver: {error_message}")]
+    RateLimited {
+        retry_after_ms: Option<u64>,
+        error_message: String,
+    },
+    #[error("Forbidden by the homeserver: {error_message}")]
+    Forbidden { error_message: String },
+    #[error("Cannot set {event_type}, check the user's power level in the room: {error_message}")]
+    StateForbidden {
+        event_type: String,
+        error_message: String,
+    },
+    #[error("Matrix API error ({status_code}): {error_message}")]
     MatrixApiError {
         status_code: reqwest::StatusCode,
         error_message: String,
     },
+    #[error("Failed to accept {0} of the invites")]
+    InvitesFailed(usize),
     #[error("Cannot generate example config file when the config file already exists")]
     ConfigAlreadyExists,
+    #[error("{0} must be provided, please see --help")]
+    MissingArgument(&'static str),
+    #[error("Failed to start the async runtime: {0}")]
+    Runtime(std::io::Error),
+    #[error("Unknown message type {0}, expected one of text, notice or emote")]
+    UnknownMessageType(String),
+    #[error("Invalid listen address {0}, expected an IP address and port such as 127.0.0.1:8080")]
+    InvalidListenAddress(String),
+    #[error("Failed to listen on {address}: {source}")]
+    Listen {
+        address: String,
+        source: std::io::Error,
+    },
+    #[error("Webhook server failed: {0}")]
+    Server(String),
+    #[error("Unknown room preset {0}, expected one of private_chat, trusted_private_chat or public_chat")]
+    UnknownRoomPreset(String),
+    #[error("{0} must be set before building a MatrixClient")]
+    MissingClientSetting(&'static str),
+    #[error("Failed to render template: {0}")]
+    Template(#[from] minijinja::Error),
+    #[error("Unknown template {0}, it must be defined in the templates section of the config")]
+    UnknownTemplate(String),
+    #[error("Failed to read {path}: {source}")]
+    ReadFile {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Invalid template variable {0}, expected KEY=VALUE")]
+    InvalidTemplateVariable(String),
+    #[error("Template variables on stdin must be a JSON object")]
+    InvalidTemplateInput,
+}
+
+impl ApiError {
+    /// Whether repeating the request that produced this error could succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ApiError::Reqwest(source) | ApiError::HttpError { source, .. } => {
+                source.is_timeout() || source.is_connect()
+            }
+            ApiError::Transport { .. }
+            | ApiError::RateLimited { .. }
+            | ApiError::RoomUnreachable { .. } => true,
+            ApiError::MatrixApiError { status_code, .. } => status_code.is_server_error(),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
-#[allow(dead_code)]
 struct MatrixErrorResponseBody {
     errcode: String,
     error: String,
     retry_after_ms: Option<u64>,
 }
+
+/// Converts a non-success homeserver response into the matching `ApiError`.
+fn matrix_error(status_code: reqwest::StatusCode, response_body: &str) -> ApiError {
+    match serde_json::from_str::<MatrixErrorResponseBody>(response_body) {
+        Ok(error_body) if error_body.errcode == "M_LIMIT_EXCEEDED" => ApiError::RateLimited {
+            retry_after_ms: error_body.retry_after_ms,
+            error_message: error_body.error,
+        },
+        Ok(error_body) if error_body.errcode == "M_FORBIDDEN" => ApiError::Forbidden {
+            error_message: error_body.error,
+        },
+        Ok(error_body) if error_body.errcode == "M_UNKNOWN_TOKEN" => ApiError::UnknownToken {
+            error_message: error_body.error,
+        },
+        Ok(error_body) => ApiError::MatrixApiError {
+            status_code,
+            error_message: error_body.error,
+        },
+        Err(_) => ApiError::MatrixApiError {
+            status_code,
+            error_message: response_body.to_owned(),
+        },
+    }
+}
+
+/// Parses a successful homeserver response, reporting bodies that do not match the spec.
+fn parse_response<T: DeserializeOwned>(url: &str, response_body: &str) -> Result<T, ApiError> {
+    serde_json::from_str(response_body).map_err(|e| ApiError::MalformedResponse {
+        url: url.to_owned(),
+        reason: e.to_string(),
+    })
+}
+
+/// Generates a transaction ID, unique per process and send, for idempotent event sends.
+pub fn new_txn_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    format!(
+        "mn{}.{}.{}",
+        millis,
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
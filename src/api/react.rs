@@ -0,0 +1,102 @@
+use serde::Serialize;
+
+use crate::config::Config;
+
+use super::send_event::send_event_content;
+use super::send_message::RelatesTo;
+use super::{ApiError, Transport};
+
+#[derive(Serialize, Debug)]
+struct ReactionSendRequestBody {
+    #[serde(rename = "m.relates_to")]
+    relates_to: RelatesTo,
+}
+
+/// Reacts to an event with `key`, typically an emoji, returning the ID of the reaction event.
+pub async fn react(
+    room: &str,
+    event_id: &str,
+    key: &str,
+    txn_id: &str,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<String, ApiError> {
+    let reaction_send_body_obj = ReactionSendRequestBody {
+        relates_to: RelatesTo::annotation(event_id, key),
+    };
+
+    send_event_content(
+        room,
+        "m.reaction",
+        &reaction_send_body_obj,
+        txn_id,
+        config,
+        transport,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        faker::lorem::en::Word,
+        Fake,
+    };
+
+    use crate::config::Config;
+
+    use super::react;
+
+    #[tokio::test]
+    async fn test_react() {
+        let mut mock_server = mockito::Server::new();
+
+        let base_url = format!("http://{}", mock_server.host_with_port());
+
+        let config = Config {
+            base_url: base_url.clone(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+
+        let room: String = Word().fake();
+        let event_id = "$alert:testmatrix";
+        let txn_id = "testtxn";
+
+        let full_reaction_url = config.get_send_event_url(room.as_str(), "m.reaction", txn_id);
+        let reaction_url = full_reaction_url
+            .strip_prefix(base_url.as_str())
+            .expect("Base URL missing from reaction url");
+        let reaction_response_body = r#"
+{
+    "event_id": "$reaction:testmatrix"
+}
+"#;
+
+        let mock_endpoint = mock_server
+            .mock("PUT", reaction_url)
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "m.relates_to": {
+                    "rel_type": "m.annotation",
+                    "event_id": event_id,
+                    "key": "✅",
+                }
+            })))
+            .with_status(200)
+            .with_body(reaction_response_body)
+            .create();
+
+        let client = reqwest::Client::new();
+
+        let func_result = react(room.as_str(), event_id, "✅", txn_id, &config, &client).await;
+
+        mock_endpoint.assert();
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert_eq!(func_result.unwrap(), "$reaction:testmatrix");
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+use super::{matrix_error, parse_response, ApiError, HttpRequest, Transport};
+
+#[derive(Serialize, Debug)]
+struct RedactRequestBody {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RedactResponseBody {
+    event_id: String,
+}
+
+/// Redacts an event, returning the ID of the redaction event.
+pub async fn redact(
+    room: &str,
+    event_id: &str,
+    reason: Option<&str>,
+    txn_id: &str,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<String, ApiError> {
+    let redact_body_obj = RedactRequestBody {
+        reason: reason.map(str::to_owned),
+    };
+    let redact_body_json = serde_json::to_string(&redact_body_obj)?;
+
+    let redact_url = config.get_redact_url(room, event_id, txn_id);
+
+    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
+
+    let response = transport
+        .send(
+            HttpRequest::put(redact_url.as_str())
+                .body(redact_body_json)
+                .bearer_auth(token.as_str()),
+        )
+        .await?;
+
+    if !response.status.is_success() {
+        return Err(matrix_error(response.status, &response.body));
+    }
+
+    let redact_response: RedactResponseBody = parse_response(&redact_url, &response.body)?;
+
+    Ok(redact_response.event_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        faker::lorem::en::{Sentence, Word},
+        Fake,
+    };
+
+    use crate::config::Config;
+
+    use super::redact;
+
+    #[tokio::test]
+    async fn test_redact() {
+        let mut mock_server = mockito::Server::new();
+
+        let base_url = format!("http://{}", mock_server.host_with_port());
+
+        let config = Config {
+            base_url: base_url.clone(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+
+        let room: String = Word().fake();
+        let event_id = "$leaked:testmatrix";
+        let reason: String = Sentence(1..2).fake();
+        let txn_id = "testtxn";
+
+        let full_redact_url = config.get_redact_url(room.as_str(), event_id, txn_id);
+        let redact_url = full_redact_url
+            .strip_prefix(base_url.as_str())
+            .expect("Base URL missing from redact url");
+        let redact_response_body = r#"
+{
+    "event_id": "$redaction:testmatrix"
+}
+"#;
+
+        let mock_endpoint = mock_server
+            .mock("PUT", redact_url)
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({ "reason": reason }),
+            ))
+            .with_status(200)
+            .with_body(redact_response_body)
+            .create();
+
+        let client = reqwest::Client::new();
+
+        let func_result = redact(
+            room.as_str(),
+            event_id,
+            Some(reason.as_str()),
+            txn_id,
+            &config,
+            &client,
+        )
+        .await;
+
+        mock_endpoint.assert();
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert_eq!(func_result.unwrap(), "$redaction:testmatrix");
+    }
+}
@@ -0,0 +1,91 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::config::Config;
+
+use super::{matrix_error, parse_response, ApiError, HttpRequest, Transport};
+
+#[derive(Deserialize, Debug)]
+struct RoomAliasResponseBody {
+    room_id: String,
+}
+
+/// Looks up the ID of the room a `#alias:server` points to.
+pub async fn resolve_room_alias(
+    alias: &str,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<String, ApiError> {
+    let room_alias_url = config.get_room_alias_url(alias);
+
+    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
+
+    let response = transport
+        .send(HttpRequest::get(room_alias_url.as_str()).bearer_auth(token.as_str()))
+        .await?;
+
+    if response.status == StatusCode::NOT_FOUND {
+        return Err(ApiError::UnknownRoomAlias(alias.to_owned()));
+    }
+    if !response.status.is_success() {
+        return Err(matrix_error(response.status, &response.body));
+    }
+
+    let room_alias_response: RoomAliasResponseBody =
+        parse_response(&room_alias_url, &response.body)?;
+
+    Ok(room_alias_response.room_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        Fake,
+    };
+
+    use matches::assert_matches;
+    use reqwest::Method;
+
+    use crate::api::{ApiError, InMemoryTransport};
+    use crate::config::Config;
+
+    use super::resolve_room_alias;
+
+    #[tokio::test]
+    async fn test_resolve_room_alias() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+
+        let transport = InMemoryTransport::new();
+        transport
+            .respond(
+                Method::GET,
+                config.get_room_alias_url("#alerts:testmatrix"),
+                200,
+                r#"{"room_id": "!alerts:testmatrix", "servers": ["testmatrix"]}"#,
+            )
+            .respond(
+                Method::GET,
+                config.get_room_alias_url("#typo:testmatrix"),
+                404,
+                r#"{"errcode": "M_NOT_FOUND", "error": "Room alias not found"}"#,
+            );
+
+        let room_id = resolve_room_alias("#alerts:testmatrix", &config, &transport).await;
+        let unknown = resolve_room_alias("#typo:testmatrix", &config, &transport).await;
+
+        assert_eq!(room_id.unwrap(), "!alerts:testmatrix");
+        assert_eq!(
+            transport.requests()[0].url,
+            "http://localhost/_matrix/client/r0/directory/room/%23alerts%3Atestmatrix"
+        );
+        assert_matches!(unknown, Err(ApiError::UnknownRoomAlias(alias)) if alias == "#typo:testmatrix");
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+use super::{matrix_error, parse_response, ApiError, HttpRequest, Transport};
+
+/// Event type used for messages, and by `send-event` when no `--type` is given.
+pub const MESSAGE_EVENT_TYPE: &str = "m.room.message";
+
+#[derive(Deserialize, Debug)]
+struct EventSendResponseBody {
+    event_id: String,
+}
+
+/// Sends a custom event of any type to a room, returning the ID of the created event.
+///
+/// Repeating a send with the same `txn_id` will not create a second event.
+pub async fn send_event(
+    room: &str,
+    event_type: &str,
+    content: &serde_json::Value,
+    txn_id: &str,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<String, ApiError> {
+    send_event_content(room, event_type, content, txn_id, config, transport).await
+}
+
+/// Sends any serializable event content, shared by the typed senders in this module.
+pub(super) async fn send_event_content<T: Serialize + ?Sized>(
+    room: &str,
+    event_type: &str,
+    content: &T,
+    txn_id: &str,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<String, ApiError> {
+    let event_send_body_json = serde_json::to_string(content).map_err(ApiError::SerdeJson)?;
+
+    let event_send_url = config.get_send_event_url(room, event_type, txn_id);
+
+    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
+
+    let response = transport
+        .send(
+            HttpRequest::put(event_send_url.as_str())
+                .body(event_send_body_json)
+                .bearer_auth(token.as_str()),
+        )
+        .await?;
+
+    if !response.status.is_success() {
+        return Err(matrix_error(response.status, &response.body));
+    }
+
+    let event_send_response: EventSendResponseBody =
+        parse_response(&event_send_url, &response.body)?;
+
+    Ok(event_send_response.event_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        faker::lorem::en::Word,
+        Fake,
+    };
+
+    use crate::config::Config;
+
+    use super::send_event;
+
+    #[tokio::test]
+    async fn test_send_custom_event() {
+        let mut mock_server = mockito::Server::new();
+
+        let base_url = format!("http://{}", mock_server.host_with_port());
+
+        let config = Config {
+            base_url: base_url.clone(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+
+        let room: String = Word().fake();
+        let event_type = "com.example.deploy";
+        let content = serde_json::json!({
+            "service": "api",
+            "version": "1.4.2",
+            "success": true,
+        });
+        let txn_id = "testtxn";
+
+        let full_send_event_url = config.get_send_event_url(room.as_str(), event_type, txn_id);
+        let send_event_url = full_send_event_url
+            .strip_prefix(base_url.as_str())
+            .expect("Base URL missing from send event url");
+        let send_event_response_body = r#"
+{
+    "event_id": "$deploy:testmatrix"
+}
+"#;
+
+        let mock_endpoint = mock_server
+            .mock("PUT", send_event_url)
+            .match_body(mockito::Matcher::Json(content.clone()))
+            .with_status(200)
+            .with_body(send_event_response_body)
+            .create();
+
+        let client = reqwest::Client::new();
+
+        let func_result = send_event(
+            room.as_str(),
+            event_type,
+            &content,
+            txn_id,
+            &config,
+            &client,
+        )
+        .await;
+
+        mock_endpoint.assert();
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert_eq!(func_result.unwrap(), "$deploy:testmatrix");
+    }
+}
@@ -1,66 +1,356 @@
-use reqwest::Client;
-use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
 
 use crate::config::Config;
 
-use super::{ApiError, MatrixErrorResponseBody};
+use super::send_event::{send_event_content, MESSAGE_EVENT_TYPE};
+use super::{ApiError, Transport};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageType {
+    #[default]
+    #[serde(alias = "m.text")]
+    Text,
+    /// Rendered subdued by clients and ignored by well behaved bots.
+    #[serde(alias = "m.notice")]
+    Notice,
+    #[serde(alias = "m.emote")]
+    Emote,
+}
+
+impl MessageType {
+    pub fn as_msgtype(&self) -> &'static str {
+        match self {
+            MessageType::Text => "m.text",
+            MessageType::Notice => "m.notice",
+            MessageType::Emote => "m.emote",
+        }
+    }
+}
+
+impl FromStr for MessageType {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" | "m.text" => Ok(MessageType::Text),
+            "notice" | "m.notice" => Ok(MessageType::Notice),
+            "emote" | "m.emote" => Ok(MessageType::Emote),
+            _ => Err(ApiError::UnknownMessageType(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_msgtype())
+    }
+}
+
+/// The content of an `m.room.message` event.
+///
+/// Literal `\n` sequences in the bodies are sent as line breaks.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Message {
+    pub msgtype: MessageType,
+    pub body: String,
+    pub html: Option<String>,
+    /// Event this message replies to.
+    pub reply_to: Option<String>,
+    /// Root event of the thread this message is posted in.
+    pub thread: Option<String>,
+    /// Event whose content this message replaces, ignoring `reply_to` and `thread`.
+    pub replaces: Option<String>,
+    /// Users pinged by this message, shown as pills in front of the message.
+    pub mentions: Vec<Mention>,
+    /// Whether this message pings everyone in the room.
+    pub mention_room: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mention {
+    pub user_id: String,
+    /// Name shown in the pill, the user ID if not set.
+    pub display_name: Option<String>,
+}
+
+impl Message {
+    pub fn text(body: impl Into<String>) -> Self {
+        Self {
+            body: body.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn html(body: impl Into<String>, html: impl Into<String>) -> Self {
+        Self {
+            html: Some(html.into()),
+            ..Self::text(body)
+        }
+    }
+
+    pub fn with_msgtype(mut self, msgtype: MessageType) -> Self {
+        self.msgtype = msgtype;
+        self
+    }
+
+    pub fn in_reply_to(mut self, event_id: impl Into<String>) -> Self {
+        self.reply_to = Some(event_id.into());
+        self
+    }
+
+    pub fn in_thread(mut self, thread_root: impl Into<String>) -> Self {
+        self.thread = Some(thread_root.into());
+        self
+    }
+
+    pub fn replacing(mut self, event_id: impl Into<String>) -> Self {
+        self.replaces = Some(event_id.into());
+        self
+    }
+
+    pub fn mentioning(mut self, user_id: impl Into<String>, display_name: Option<String>) -> Self {
+        self.mentions.push(Mention {
+            user_id: user_id.into(),
+            display_name,
+        });
+        self
+    }
+
+    pub fn mentioning_room(mut self) -> Self {
+        self.mention_room = true;
+        self
+    }
+
+    /// Plain text and HTML prefixes naming everyone mentioned, empty without mentions.
+    fn mention_prefixes(&self) -> (String, String) {
+        let mut names = Vec::new();
+        let mut pills = Vec::new();
+        if self.mention_room {
+            names.push("@room".to_owned());
+            pills.push("@room".to_owned());
+        }
+        for mention in &self.mentions {
+            let name = mention
+                .display_name
+                .as_deref()
+                .unwrap_or(mention.user_id.as_str());
+            names.push(name.to_owned());
+            pills.push(format!(
+                r#"<a href="https://matrix.to/#/{}">{}</a>"#,
+                escape_html(&mention.user_id),
+                escape_html(name)
+            ));
+        }
+
+        if names.is_empty() {
+            return (String::new(), String::new());
+        }
+        (
+            format!("{}: ", names.join(", ")),
+            format!("{}: ", pills.join(", ")),
+        )
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct Mentions {
+    user_ids: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    room: bool,
+}
+impl Mentions {
+    fn new(message: &Message) -> Option<Self> {
+        if message.mentions.is_empty() && !message.mention_room {
+            return None;
+        }
+        Some(Self {
+            user_ids: message
+                .mentions
+                .iter()
+                .map(|mention| mention.user_id.clone())
+                .collect(),
+            room: message.mention_room,
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+pub(super) struct RelatesTo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rel_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_falling_back: Option<bool>,
+    #[serde(rename = "m.in_reply_to", skip_serializing_if = "Option::is_none")]
+    in_reply_to: Option<InReplyTo>,
+}
+impl RelatesTo {
+    pub(super) fn annotation(event_id: &str, key: &str) -> Self {
+        Self {
+            rel_type: Some("m.annotation".to_owned()),
+            event_id: Some(event_id.to_owned()),
+            key: Some(key.to_owned()),
+            ..Self::default()
+        }
+    }
+
+    fn replace(event_id: &str) -> Self {
+        Self {
+            rel_type: Some("m.replace".to_owned()),
+            event_id: Some(event_id.to_owned()),
+            ..Self::default()
+        }
+    }
+
+    fn new(reply_to: Option<&str>, thread: Option<&str>) -> Option<Self> {
+        match (reply_to, thread) {
+            (None, None) => None,
+            (Some(reply_to), None) => Some(Self {
+                in_reply_to: Some(InReplyTo::new(reply_to)),
+                ..Self::default()
+            }),
+            // Clients without thread support render threaded messages as replies,
+            // to the thread root unless a specific event is being replied to.
+            (reply_to, Some(thread)) => Some(Self {
+                rel_type: Some("m.thread".to_owned()),
+                event_id: Some(thread.to_owned()),
+                is_falling_back: Some(reply_to.is_none()),
+                in_reply_to: Some(InReplyTo::new(reply_to.unwrap_or(thread))),
+                ..Self::default()
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct InReplyTo {
+    event_id: String,
+}
+impl InReplyTo {
+    fn new(event_id: &str) -> Self {
+        Self {
+            event_id: event_id.to_owned(),
+        }
+    }
+}
 
 #[derive(Serialize, Debug)]
 struct MessageSendRequestBody {
     msgtype: String,
     body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    formatted_body: Option<String>,
+    #[serde(rename = "m.relates_to", skip_serializing_if = "Option::is_none")]
+    relates_to: Option<RelatesTo>,
+    #[serde(rename = "m.mentions", skip_serializing_if = "Option::is_none")]
+    mentions: Option<Mentions>,
+    #[serde(rename = "m.new_content", skip_serializing_if = "Option::is_none")]
+    new_content: Option<Box<MessageSendRequestBody>>,
 }
 impl MessageSendRequestBody {
-    fn new(body: &str) -> Self {
+    fn new(message: &Message) -> Self {
+        match &message.replaces {
+            Some(event_id) => Self::new_replacement(message, event_id),
+            None => Self::new_content(message),
+        }
+    }
+
+    /// Clients without edit support display the fallback body, marked with a leading `*`.
+    fn new_replacement(message: &Message, event_id: &str) -> Self {
+        let new_content = Self::new_content(&Message {
+            reply_to: None,
+            thread: None,
+            replaces: None,
+            ..message.clone()
+        });
         Self {
-            msgtype: "m.text".to_owned(),
-            body: body.to_owned().replace("\\n", "\n").replace("\\\\", "\\"),
+            msgtype: new_content.msgtype.clone(),
+            body: format!("* {}", new_content.body),
+            format: new_content.format.clone(),
+            formatted_body: new_content
+                .formatted_body
+                .as_ref()
+                .map(|formatted_body| format!("* {}", formatted_body)),
+            relates_to: Some(RelatesTo::replace(event_id)),
+            mentions: new_content.mentions.clone(),
+            new_content: Some(Box::new(new_content)),
         }
     }
+
+    fn new_content(message: &Message) -> Self {
+        let body = unescape(&message.body);
+        let mut formatted_body = message.html.as_deref().map(unescape);
+
+        let mentions = Mentions::new(message);
+        let (body_prefix, html_prefix) = message.mention_prefixes();
+        if mentions.is_some() {
+            // Pills only exist in HTML, so mentions always need a formatted body.
+            let html = formatted_body.unwrap_or_else(|| escape_html(&body).replace('\n', "<br>"));
+            formatted_body = Some(format!("{}{}", html_prefix, html));
+        }
+
+        Self {
+            msgtype: message.msgtype.as_msgtype().to_owned(),
+            body: format!("{}{}", body_prefix, body),
+            format: formatted_body
+                .as_ref()
+                .map(|_| "org.matrix.custom.html".to_owned()),
+            formatted_body,
+            relates_to: RelatesTo::new(message.reply_to.as_deref(), message.thread.as_deref()),
+            mentions,
+            new_content: None,
+        }
+    }
+}
+
+/// An event created by a send, along with the transaction ID it was sent with.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SentEvent {
+    pub room_id: String,
+    pub event_id: String,
+    pub txn_id: String,
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
 }
 
+fn unescape(text: &str) -> String {
+    text.replace("\\n", "\n").replace("\\\\", "\\")
+}
+
+/// Sends a message, returning the ID of the created event.
+///
+/// Repeating a send with the same `txn_id` will not create a second event.
 pub async fn send_message(
-    message: &str,
+    message: &Message,
     room: &str,
+    txn_id: &str,
     config: &Config,
-    client: &Client,
-) -> Result<(), ApiError> {
-    let message_send_body_obj = MessageSendRequestBody::new(message);
-    let message_send_body_json =
-        serde_json::to_string(&message_send_body_obj).map_err(ApiError::SerdeJson)?;
-
-    let message_send_url = config.get_send_message_url(room);
-
-    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
-
-    let response = client
-        .post(message_send_url.clone())
-        .body(message_send_body_json)
-        .bearer_auth(token.as_str())
-        .send()
-        .await
-        .map_err(|e| ApiError::HttpError {
-            source: e,
-            url: message_send_url.clone(),
-        })?;
-
-    let response_status = response.status();
-    let message_send_response = response.text().await.map_err(|err| ApiError::HttpError {
-        source: err,
-        url: message_send_url.clone(),
-    })?;
-
-    if !response_status.is_success() {
-        let error_message: MatrixErrorResponseBody =
-            serde_json::from_str(&message_send_response).map_err(ApiError::SerdeJson)?;
-
-        return Err(ApiError::MatrixApiError {
-            status_code: response_status,
-            error_message: error_message.error,
-        });
-    }
-
-    Ok(())
+    transport: &dyn Transport,
+) -> Result<String, ApiError> {
+    send_event_content(
+        room,
+        MESSAGE_EVENT_TYPE,
+        &MessageSendRequestBody::new(message),
+        txn_id,
+        config,
+        transport,
+    )
+    .await
 }
 
 #[cfg(test)]
@@ -71,9 +361,30 @@ mod tests {
         Fake,
     };
 
+    use reqwest::Method;
+
+    use crate::api::InMemoryTransport;
     use crate::config::Config;
 
-    use super::send_message;
+    use super::{send_message, Message, MessageType};
+
+    const TXN_ID: &str = "testtxn";
+
+    #[test]
+    fn test_message_type_round_trip() {
+        for msgtype in [MessageType::Text, MessageType::Notice, MessageType::Emote] {
+            assert_eq!(msgtype.to_string().parse::<MessageType>().unwrap(), msgtype);
+            assert_eq!(
+                serde_json::from_value::<MessageType>(serde_json::json!(msgtype.to_string()))
+                    .unwrap(),
+                msgtype
+            );
+        }
+        assert_eq!(
+            "notice".parse::<MessageType>().unwrap(),
+            MessageType::Notice
+        );
+    }
 
     #[tokio::test]
     async fn test_send_message_room() {
@@ -87,34 +398,253 @@ mod tests {
             full_username: Username().fake(),
             password: None,
             token: Some(Password(16..24).fake()),
+            ..Default::default()
         };
 
         let room: String = Word().fake();
         let message: String = Sentence(1..2).fake();
 
-        let full_send_message_url = config.get_send_message_url(room.as_str());
+        let full_send_message_url = config.get_send_message_url(room.as_str(), TXN_ID);
         let send_message_url = full_send_message_url
             .strip_prefix(base_url.as_str())
             .expect("Base URL missing from profile url");
+        let event_id = "$testevent:testmatrix";
         let send_message_response_body = format!(
             r#"
 {{
+    "event_id": "{}"
 }}
 "#,
+            event_id
         );
 
         let mock_endpoint = mock_server
-            .mock("POST", send_message_url)
+            .mock("PUT", send_message_url)
             .with_status(200)
             .with_body(send_message_response_body.as_str())
             .create();
 
         let client = reqwest::Client::new();
 
-        let func_result = send_message(message.as_str(), room.as_str(), &config, &client).await;
+        let func_result = send_message(
+            &Message::text(message),
+            room.as_str(),
+            TXN_ID,
+            &config,
+            &client,
+        )
+        .await;
+
+        mock_endpoint.assert();
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert_eq!(func_result.unwrap(), event_id);
+    }
+
+    #[tokio::test]
+    async fn test_send_html_notice_room() {
+        let mut mock_server = mockito::Server::new();
+
+        let base_url = format!("http://{}", mock_server.host_with_port());
+
+        let config = Config {
+            base_url: base_url.clone(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+
+        let room: String = Word().fake();
+        let message: String = Sentence(1..2).fake();
+        let html = format!("<b>{}</b>", message);
+
+        let full_send_message_url = config.get_send_message_url(room.as_str(), TXN_ID);
+        let send_message_url = full_send_message_url
+            .strip_prefix(base_url.as_str())
+            .expect("Base URL missing from profile url");
+
+        let mock_endpoint = mock_server
+            .mock("PUT", send_message_url)
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "msgtype": "m.notice",
+                "body": message,
+                "format": "org.matrix.custom.html",
+                "formatted_body": html,
+            })))
+            .with_status(200)
+            .with_body(r#"{"event_id": "$testevent:testmatrix"}"#)
+            .create();
+
+        let client = reqwest::Client::new();
+
+        let func_result = send_message(
+            &Message::html(message, html).with_msgtype(MessageType::Notice),
+            room.as_str(),
+            TXN_ID,
+            &config,
+            &client,
+        )
+        .await;
 
         mock_endpoint.assert();
 
         assert!(func_result.is_ok(), "{:?}", func_result);
     }
+
+    #[tokio::test]
+    async fn test_send_threaded_reply() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+
+        let room: String = Word().fake();
+        let message: String = Sentence(1..2).fake();
+
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::PUT,
+            config.get_send_message_url(room.as_str(), TXN_ID),
+            200,
+            r#"{"event_id": "$reply:testmatrix"}"#,
+        );
+
+        let func_result = send_message(
+            &Message::text(message)
+                .in_thread("$root:testmatrix")
+                .in_reply_to("$previous:testmatrix"),
+            room.as_str(),
+            TXN_ID,
+            &config,
+            &transport,
+        )
+        .await;
+
+        assert_eq!(func_result.unwrap(), "$reply:testmatrix");
+
+        let sent_body: serde_json::Value =
+            serde_json::from_str(transport.requests()[0].body.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            sent_body["m.relates_to"],
+            serde_json::json!({
+                "rel_type": "m.thread",
+                "event_id": "$root:testmatrix",
+                "is_falling_back": false,
+                "m.in_reply_to": { "event_id": "$previous:testmatrix" },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_edit() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+
+        let room: String = Word().fake();
+
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::PUT,
+            config.get_send_message_url(room.as_str(), TXN_ID),
+            200,
+            r#"{"event_id": "$edit:testmatrix"}"#,
+        );
+
+        let func_result = send_message(
+            &Message::html("Deploy 40%", "<b>Deploy</b> 40%").replacing("$original:testmatrix"),
+            room.as_str(),
+            TXN_ID,
+            &config,
+            &transport,
+        )
+        .await;
+
+        assert_eq!(func_result.unwrap(), "$edit:testmatrix");
+
+        let sent_body: serde_json::Value =
+            serde_json::from_str(transport.requests()[0].body.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            sent_body,
+            serde_json::json!({
+                "msgtype": "m.text",
+                "body": "* Deploy 40%",
+                "format": "org.matrix.custom.html",
+                "formatted_body": "* <b>Deploy</b> 40%",
+                "m.new_content": {
+                    "msgtype": "m.text",
+                    "body": "Deploy 40%",
+                    "format": "org.matrix.custom.html",
+                    "formatted_body": "<b>Deploy</b> 40%",
+                },
+                "m.relates_to": {
+                    "rel_type": "m.replace",
+                    "event_id": "$original:testmatrix",
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_mentions() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+
+        let room: String = Word().fake();
+
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::PUT,
+            config.get_send_message_url(room.as_str(), TXN_ID),
+            200,
+            r#"{"event_id": "$alert:testmatrix"}"#,
+        );
+
+        let func_result = send_message(
+            &Message::text("Disk <90%> full")
+                .mentioning("@alice:testmatrix", Some("Alice".to_owned()))
+                .mentioning("@bob:testmatrix", None)
+                .mentioning_room(),
+            room.as_str(),
+            TXN_ID,
+            &config,
+            &transport,
+        )
+        .await;
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+
+        let sent_body: serde_json::Value =
+            serde_json::from_str(transport.requests()[0].body.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            sent_body,
+            serde_json::json!({
+                "msgtype": "m.text",
+                "body": "@room, Alice, @bob:testmatrix: Disk <90%> full",
+                "format": "org.matrix.custom.html",
+                "formatted_body": "@room, <a href=\"https://matrix.to/#/@alice:testmatrix\">Alice</a>, <a href=\"https://matrix.to/#/@bob:testmatrix\">@bob:testmatrix</a>: Disk &lt;90%&gt; full",
+                "m.mentions": {
+                    "user_ids": ["@alice:testmatrix", "@bob:testmatrix"],
+                    "room": true,
+                },
+            })
+        );
+    }
 }
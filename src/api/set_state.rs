@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+use super::{matrix_error, parse_response, ApiError, HttpRequest, Transport};
+
+/// A state event set in a room.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StateEvent {
+    pub room_id: String,
+    pub event_id: String,
+    pub event_type: String,
+    pub state_key: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SetStateResponseBody {
+    event_id: String,
+}
+
+/// Sets the state of a room, such as its `m.room.topic`, returning the ID of the state event.
+///
+/// Most state types use an empty `state_key`. Fails with `ApiError::StateForbidden`
+/// when the user lacks the power level to change this state.
+pub async fn set_state(
+    room: &str,
+    event_type: &str,
+    state_key: &str,
+    content: &serde_json::Value,
+    config: &Config,
+    transport: &dyn Transport,
+) -> Result<String, ApiError> {
+    let state_body_json = serde_json::to_string(content)?;
+
+    let state_url = config.get_room_state_url(room, event_type, state_key);
+
+    let token = config.token.clone().ok_or(ApiError::MissingToken)?;
+
+    let response = transport
+        .send(
+            HttpRequest::put(state_url.as_str())
+                .body(state_body_json)
+                .bearer_auth(token.as_str()),
+        )
+        .await?;
+
+    if !response.status.is_success() {
+        return Err(match matrix_error(response.status, &response.body) {
+            ApiError::Forbidden { error_message } => ApiError::StateForbidden {
+                event_type: event_type.to_owned(),
+                error_message,
+            },
+            e => e,
+        });
+    }
+
+    let state_response: SetStateResponseBody = parse_response(&state_url, &response.body)?;
+
+    Ok(state_response.event_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        faker::lorem::en::{Sentence, Word},
+        Fake,
+    };
+    use matches::assert_matches;
+
+    use crate::api::ApiError;
+    use crate::config::Config;
+
+    use super::set_state;
+
+    fn token_config(base_url: &str) -> Config {
+        Config {
+            base_url: base_url.to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_topic() {
+        let mut mock_server = mockito::Server::new();
+
+        let base_url = format!("http://{}", mock_server.host_with_port());
+        let config = token_config(&base_url);
+
+        let room: String = Word().fake();
+        let topic: String = Sentence(1..2).fake();
+        let content = serde_json::json!({ "topic": topic });
+
+        let full_state_url = config.get_room_state_url(room.as_str(), "m.room.topic", "");
+        let state_url = full_state_url
+            .strip_prefix(base_url.as_str())
+            .expect("Base URL missing from room state url");
+
+        let mock_endpoint = mock_server
+            .mock("PUT", state_url)
+            .match_body(mockito::Matcher::Json(content.clone()))
+            .with_status(200)
+            .with_body(r#"{"event_id": "$topic:testmatrix"}"#)
+            .create();
+
+        let client = reqwest::Client::new();
+
+        let func_result = set_state(
+            room.as_str(),
+            "m.room.topic",
+            "",
+            &content,
+            &config,
+            &client,
+        )
+        .await;
+
+        mock_endpoint.assert();
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert_eq!(func_result.unwrap(), "$topic:testmatrix");
+    }
+
+    #[tokio::test]
+    async fn test_set_state_forbidden() {
+        let mut mock_server = mockito::Server::new();
+
+        let base_url = format!("http://{}", mock_server.host_with_port());
+        let config = token_config(&base_url);
+
+        let room: String = Word().fake();
+
+        let full_state_url = config.get_room_state_url(room.as_str(), "m.room.name", "");
+        let state_url = full_state_url
+            .strip_prefix(base_url.as_str())
+            .expect("Base URL missing from room state url");
+
+        let mock_endpoint = mock_server
+            .mock("PUT", state_url)
+            .with_status(403)
+            .with_body(
+                r#"{"errcode": "M_FORBIDDEN", "error": "You don't have permission to post that to the room."}"#,
+            )
+            .create();
+
+        let client = reqwest::Client::new();
+
+        let func_result = set_state(
+            room.as_str(),
+            "m.room.name",
+            "",
+            &serde_json::json!({ "name": "Status" }),
+            &config,
+            &client,
+        )
+        .await;
+
+        mock_endpoint.assert();
+
+        assert_matches!(
+            func_result,
+            Err(ApiError::StateForbidden { event_type, .. }) if event_type == "m.room.name"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use reqwest::{Method, StatusCode};
+
+use super::ApiError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub bearer_token: Option<String>,
+    pub body: Option<String>,
+}
+
+impl HttpRequest {
+    pub fn new(method: Method, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            bearer_token: None,
+            body: None,
+        }
+    }
+
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(Method::GET, url)
+    }
+
+    pub fn post(url: impl Into<String>) -> Self {
+        Self::new(Method::POST, url)
+    }
+
+    pub fn put(url: impl Into<String>) -> Self {
+        Self::new(Method::PUT, url)
+    }
+
+    pub fn bearer_auth(mut self, token: impl Into<String>) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn new(status: StatusCode, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+        }
+    }
+}
+
+/// The HTTP stack used by the `api` functions.
+///
+/// Implement this to route requests through your own client or middleware.
+/// Failures to reach the server should be reported as `ApiError::Transport`
+/// (or `ApiError::HttpError` for reqwest based stacks); non-success statuses
+/// are returned as regular responses and interpreted by the caller.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, ApiError>;
+}
+
+#[async_trait]
+impl Transport for reqwest::Client {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, ApiError> {
+        let mut builder = self.request(request.method, request.url.as_str());
+        if let Some(token) = request.bearer_token {
+            builder = builder.bearer_auth(token);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+
+        let response = builder.send().await.map_err(|e| ApiError::HttpError {
+            source: e,
+            url: request.url.clone(),
+        })?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| ApiError::HttpError {
+            source: e,
+            url: request.url.clone(),
+        })?;
+
+        Ok(HttpResponse { status, body })
+    }
+}
+
+/// Shares a transport, e.g. to inspect the requests of an `InMemoryTransport` handed to a client.
+#[async_trait]
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, ApiError> {
+        self.as_ref().send(request).await
+    }
+}
+
+/// A `Transport` answering from canned responses, for tests that should not open sockets.
+///
+/// Responses registered for the same method and URL are returned in order,
+/// with the last one repeated once the others are used up.
+/// Requests without a registered response receive a `404 M_UNRECOGNIZED`.
+#[derive(Debug, Default)]
+pub struct InMemoryTransport {
+    routes: Mutex<Vec<(Method, String, VecDeque<HttpResponse>)>>,
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl InMemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn respond(
+        &self,
+        method: Method,
+        url: impl Into<String>,
+        status: u16,
+        body: impl Into<String>,
+    ) -> &Self {
+        let url = url.into();
+        let response = HttpResponse::new(
+            StatusCode::from_u16(status).expect("Invalid HTTP status code"),
+            body,
+        );
+
+        let mut routes = self.routes.lock().expect("InMemoryTransport lock poisoned");
+        match routes
+            .iter_mut()
+            .find(|(route_method, route_url, _)| *route_method == method && *route_url == url)
+        {
+            Some((_, _, responses)) => responses.push_back(response),
+            None => routes.push((method, url, VecDeque::from([response]))),
+        }
+        self
+    }
+
+    /// Every request sent so far, oldest first.
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests
+            .lock()
+            .expect("InMemoryTransport lock poisoned")
+            .clone()
+    }
+}
+
+#[async_trait]
+impl Transport for InMemoryTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, ApiError> {
+        let mut routes = self.routes.lock().expect("InMemoryTransport lock poisoned");
+        let response = routes
+            .iter_mut()
+            .find(|(method, url, _)| *method == request.method && *url == request.url)
+            .and_then(|(_, _, responses)| {
+                if responses.len() > 1 {
+                    responses.pop_front()
+                } else {
+                    responses.front().cloned()
+                }
+            })
+            .unwrap_or_else(|| {
+                HttpResponse::new(
+                    StatusCode::NOT_FOUND,
+                    r#"{"errcode": "M_UNRECOGNIZED", "error": "Unrecognized request"}"#,
+                )
+            });
+
+        self.requests
+            .lock()
+            .expect("InMemoryTransport lock poisoned")
+            .push(request);
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Method, StatusCode};
+
+    use super::{HttpRequest, InMemoryTransport, Transport};
+
+    #[tokio::test]
+    async fn test_in_memory_transport_replays_responses_in_order() {
+        let transport = InMemoryTransport::new();
+        transport
+            .respond(Method::GET, "http://localhost/first", 429, "{}")
+            .respond(Method::GET, "http://localhost/first", 200, "{}");
+
+        let statuses = [
+            transport
+                .send(HttpRequest::get("http://localhost/first"))
+                .await
+                .unwrap()
+                .status,
+            transport
+                .send(HttpRequest::get("http://localhost/first"))
+                .await
+                .unwrap()
+                .status,
+            transport
+                .send(HttpRequest::get("http://localhost/first"))
+                .await
+                .unwrap()
+                .status,
+            transport
+                .send(HttpRequest::post("http://localhost/first"))
+                .await
+                .unwrap()
+                .status,
+        ];
+
+        assert_eq!(
+            statuses,
+            [
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::NOT_FOUND
+            ]
+        );
+        assert_eq!(transport.requests().len(), 4);
+    }
+}
@@ -1,34 +1,112 @@
-use reqwest::Client;
-use serde_json::Value;
-
 use crate::config::Config;
 
-use super::ApiError;
+use super::{get_joined_rooms, ApiError, Transport};
 
+/// Whether the user has joined `room`, using the compact `/joined_rooms` listing
+/// rather than downloading the members of the room.
 pub async fn verify_in_room(
     room: &str,
     config: &Config,
-    client: &Client,
+    transport: &dyn Transport,
 ) -> Result<bool, ApiError> {
-    match &config.token {
-        Some(token) => {
-            let join_url = config.get_room_members_url(room);
-            let join_response = client
-                .get(join_url)
-                .bearer_auth(token.as_str())
-                .send()
-                .await?;
-
-            if join_response.status().is_success() {
-                let join_response_json: Value = join_response.json().await?;
-
-                let user_id = &config.full_username;
-                let members = join_response_json["joined"].as_object().unwrap();
-
-                return Ok(members.iter().any(|(k, _v)| k == user_id));
-            }
-        }
-        None => {}
+    let joined_rooms = get_joined_rooms(config, transport).await?;
+
+    Ok(joined_rooms.iter().any(|joined_room| joined_room == room))
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        faker::lorem::en::Word,
+        Fake,
+    };
+    use matches::assert_matches;
+    use reqwest::Method;
+
+    use crate::api::{ApiError, InMemoryTransport};
+    use crate::config::Config;
+
+    use super::verify_in_room;
+
+    #[tokio::test]
+    async fn test_verify_in_room() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let room: String = Word().fake();
+
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::GET,
+            config.get_joined_rooms_url(),
+            200,
+            format!(r#"{{"joined_rooms": ["!other:testmatrix", "{}"]}}"#, room),
+        );
+
+        let func_result = verify_in_room(room.as_str(), &config, &transport).await;
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert!(func_result.unwrap());
+        assert_eq!(
+            transport.requests()[0].bearer_token.as_deref(),
+            config.token.as_deref()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_not_in_room() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let room: String = Word().fake();
+
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::GET,
+            config.get_joined_rooms_url(),
+            200,
+            r#"{"joined_rooms": ["!other:testmatrix"]}"#,
+        );
+
+        let func_result = verify_in_room(room.as_str(), &config, &transport).await;
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert!(!func_result.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_verify_in_room_malformed_joined_rooms() {
+        let config = Config {
+            base_url: "http://localhost".to_owned(),
+            local_username: Username().fake(),
+            full_username: Username().fake(),
+            password: None,
+            token: Some(Password(16..24).fake()),
+            ..Default::default()
+        };
+        let room: String = Word().fake();
+
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::GET,
+            config.get_joined_rooms_url(),
+            200,
+            r#"{"joined_rooms": {}}"#,
+        );
+
+        let func_result = verify_in_room(room.as_str(), &config, &transport).await;
+
+        assert_matches!(func_result.unwrap_err(), ApiError::MalformedResponse { .. });
     }
-    Ok(false)
 }
@@ -1,33 +1,20 @@
 use crate::config::Config;
 
-use super::ApiError;
+use super::{matrix_error, ApiError, HttpRequest, Transport};
 
 pub async fn verify_token(
     token: &str,
     config: &Config,
-    client: &reqwest::Client,
+    transport: &dyn Transport,
 ) -> Result<String, ApiError> {
     let profile_url = config.get_profile_url();
 
-    let profile_url_clone = profile_url.clone();
-
-    let response = client
-        .get(profile_url)
-        .bearer_auth(token)
-        .send()
-        .await
-        .map_err(|err| ApiError::HttpError {
-            source: err,
-            url: profile_url_clone,
-        })?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let text = response.text().await.unwrap_or_default();
-        return Err(ApiError::MatrixApiError {
-            status_code: status,
-            error_message: text,
-        });
+    let response = transport
+        .send(HttpRequest::get(profile_url).bearer_auth(token))
+        .await?;
+
+    if !response.status.is_success() {
+        return Err(matrix_error(response.status, &response.body));
     }
 
     Ok(token.to_owned())
@@ -56,6 +43,7 @@ mod tests {
             full_username: Username().fake(),
             password: None,
             token: Some(Password(16..24).fake()),
+            ..Default::default()
         };
 
         let full_profile_url = config.get_profile_url();
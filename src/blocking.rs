@@ -0,0 +1,233 @@
+//! A synchronous wrapper around `MatrixClient`, enabled by the `blocking` feature.
+//!
+//! Each call drives the async client to completion on a private current-thread runtime,
+//! so these methods must not be called from within an async context.
+
+use tokio::runtime::{Builder, Runtime};
+
+use crate::api::{ApiError, Invite, Message, NewRoom, SentEvent, StateEvent};
+use crate::client::{InviteOutcome, MatrixClientBuilder};
+
+impl MatrixClientBuilder {
+    pub fn build_blocking(self) -> Result<MatrixClient, ApiError> {
+        MatrixClient::new(self.build()?)
+    }
+}
+
+#[derive(Debug)]
+pub struct MatrixClient {
+    inner: crate::MatrixClient,
+    runtime: Runtime,
+}
+
+impl MatrixClient {
+    pub fn builder() -> MatrixClientBuilder {
+        crate::MatrixClient::builder()
+    }
+
+    pub fn new(inner: crate::MatrixClient) -> Result<Self, ApiError> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(ApiError::Runtime)?;
+        Ok(Self { inner, runtime })
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.inner.token()
+    }
+
+    pub fn set_token(&mut self, token: impl Into<String>) {
+        self.inner.set_token(token)
+    }
+
+    pub fn login(&mut self) -> Result<String, ApiError> {
+        self.runtime.block_on(self.inner.login())
+    }
+
+    pub fn verify_token(&self) -> Result<String, ApiError> {
+        self.runtime.block_on(self.inner.verify_token())
+    }
+
+    pub fn display_name(&self, user_id: &str) -> Result<Option<String>, ApiError> {
+        self.runtime.block_on(self.inner.display_name(user_id))
+    }
+
+    pub fn joined_rooms(&self) -> Result<Vec<String>, ApiError> {
+        self.runtime.block_on(self.inner.joined_rooms())
+    }
+
+    pub fn is_member(&self, room: &str) -> Result<bool, ApiError> {
+        self.runtime.block_on(self.inner.is_member(room))
+    }
+
+    pub fn join(&self, room: &str) -> Result<(), ApiError> {
+        self.runtime.block_on(self.inner.join(room))
+    }
+
+    pub fn join_via(&self, room: &str, via: &[String]) -> Result<(), ApiError> {
+        self.runtime.block_on(self.inner.join_via(room, via))
+    }
+
+    pub fn room_id(&self, room: &str) -> Result<String, ApiError> {
+        self.runtime.block_on(self.inner.room_id(room))
+    }
+
+    pub fn invite(&self, room: &str, user_id: &str, reason: Option<&str>) -> Result<(), ApiError> {
+        self.runtime
+            .block_on(self.inner.invite(room, user_id, reason))
+    }
+
+    pub fn kick(&self, room: &str, user_id: &str, reason: Option<&str>) -> Result<(), ApiError> {
+        self.runtime
+            .block_on(self.inner.kick(room, user_id, reason))
+    }
+
+    pub fn leave(&self, room: &str, reason: Option<&str>) -> Result<(), ApiError> {
+        self.runtime.block_on(self.inner.leave(room, reason))
+    }
+
+    pub fn forget(&self, room: &str) -> Result<(), ApiError> {
+        self.runtime.block_on(self.inner.forget(room))
+    }
+
+    pub fn invites(&self) -> Result<Vec<Invite>, ApiError> {
+        self.runtime.block_on(self.inner.invites())
+    }
+
+    pub fn accept_invites(&self) -> Result<Vec<InviteOutcome>, ApiError> {
+        self.runtime.block_on(self.inner.accept_invites())
+    }
+
+    pub fn create_room(&self, room: &NewRoom) -> Result<String, ApiError> {
+        self.runtime.block_on(self.inner.create_room(room))
+    }
+
+    pub fn direct_room(&self, user_id: &str) -> Result<String, ApiError> {
+        self.runtime.block_on(self.inner.direct_room(user_id))
+    }
+
+    pub fn send(&self, room: &str, message: &Message) -> Result<SentEvent, ApiError> {
+        self.runtime.block_on(self.inner.send(room, message))
+    }
+
+    pub fn send_joining(
+        &self,
+        room: &str,
+        via: &[String],
+        message: &Message,
+    ) -> Result<SentEvent, ApiError> {
+        self.runtime
+            .block_on(self.inner.send_joining(room, via, message))
+    }
+
+    pub fn send_event(
+        &self,
+        room: &str,
+        event_type: &str,
+        content: &serde_json::Value,
+    ) -> Result<SentEvent, ApiError> {
+        self.runtime
+            .block_on(self.inner.send_event(room, event_type, content))
+    }
+
+    pub fn set_state(
+        &self,
+        room: &str,
+        event_type: &str,
+        state_key: &str,
+        content: &serde_json::Value,
+    ) -> Result<StateEvent, ApiError> {
+        self.runtime
+            .block_on(self.inner.set_state(room, event_type, state_key, content))
+    }
+
+    pub fn set_topic(&self, room: &str, topic: &str) -> Result<StateEvent, ApiError> {
+        self.runtime.block_on(self.inner.set_topic(room, topic))
+    }
+
+    pub fn set_name(&self, room: &str, name: &str) -> Result<StateEvent, ApiError> {
+        self.runtime.block_on(self.inner.set_name(room, name))
+    }
+
+    pub fn edit(
+        &self,
+        room: &str,
+        event_id: &str,
+        message: &Message,
+    ) -> Result<SentEvent, ApiError> {
+        self.runtime
+            .block_on(self.inner.edit(room, event_id, message))
+    }
+
+    pub fn redact(
+        &self,
+        room: &str,
+        event_id: &str,
+        reason: Option<&str>,
+    ) -> Result<SentEvent, ApiError> {
+        self.runtime
+            .block_on(self.inner.redact(room, event_id, reason))
+    }
+
+    pub fn react(&self, room: &str, event_id: &str, key: &str) -> Result<SentEvent, ApiError> {
+        self.runtime.block_on(self.inner.react(room, event_id, key))
+    }
+
+    pub fn send_text(&self, room: &str, message: &str) -> Result<SentEvent, ApiError> {
+        self.runtime.block_on(self.inner.send_text(room, message))
+    }
+
+    pub fn send_html(&self, room: &str, message: &str, html: &str) -> Result<SentEvent, ApiError> {
+        self.runtime
+            .block_on(self.inner.send_html(room, message, html))
+    }
+
+    pub fn send_notice(&self, room: &str, message: &str) -> Result<SentEvent, ApiError> {
+        self.runtime.block_on(self.inner.send_notice(room, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password, Username},
+        faker::lorem::en::{Sentence, Word},
+        Fake,
+    };
+
+    use crate::api::mock_server::{path_matcher, ANY_TXN_ID};
+
+    use super::MatrixClient;
+
+    #[test]
+    fn test_blocking_send_text() {
+        let mut mock_server = mockito::Server::new();
+
+        let base_url = format!("http://{}", mock_server.host_with_port());
+
+        let client = MatrixClient::builder()
+            .base_url(base_url.as_str())
+            .user(Username().fake::<String>(), Username().fake::<String>())
+            .token(Password(16..24).fake::<String>())
+            .build_blocking()
+            .unwrap();
+
+        let room: String = Word().fake();
+        let message: String = Sentence(1..2).fake();
+
+        let send_message_url = crate::config::build_send_message_url(&base_url, &room, ANY_TXN_ID);
+
+        let mock_endpoint = mock_server
+            .mock("PUT", path_matcher(&base_url, &send_message_url))
+            .with_status(200)
+            .with_body(r#"{"event_id": "$testevent:testmatrix"}"#)
+            .create();
+
+        let func_result = client.send_text(room.as_str(), message.as_str());
+
+        mock_endpoint.assert();
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+    }
+}
@@ -0,0 +1,841 @@
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::api::{
+    self, ApiError, Invite, Message, MessageType, NewRoom, SentEvent, StateEvent, Transport,
+};
+use crate::config::Config;
+
+/// How often and how patiently a `MatrixClient` repeats failed requests.
+///
+/// Only errors reported as retryable by `ApiError::is_retryable` are repeated.
+/// When the homeserver rate limits a request, its `retry_after_ms` hint takes
+/// precedence over the computed backoff.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    fn backoff(&self, attempt: u32, error: &ApiError) -> Duration {
+        let backoff = match error {
+            ApiError::RateLimited {
+                retry_after_ms: Some(retry_after_ms),
+                ..
+            } => Duration::from_millis(*retry_after_ms),
+            _ => self
+                .initial_backoff
+                .saturating_mul(2u32.saturating_pow(attempt)),
+        };
+        backoff.min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MatrixClientBuilder {
+    base_url: Option<String>,
+    local_username: Option<String>,
+    full_username: Option<String>,
+    password: Option<String>,
+    token: Option<String>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
+    transport: Option<Box<dyn Transport>>,
+}
+
+impl MatrixClientBuilder {
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Sets the bot user, both as the localpart used to log in
+    /// and as the fully qualified `@user:server` ID.
+    pub fn user(
+        mut self,
+        local_username: impl Into<String>,
+        full_username: impl Into<String>,
+    ) -> Self {
+        self.local_username = Some(local_username.into());
+        self.full_username = Some(full_username.into());
+        self
+    }
+
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Replaces the default reqwest transport, in which case the timeouts are not applied.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Box::new(transport));
+        self
+    }
+
+    pub fn build(self) -> Result<MatrixClient, ApiError> {
+        let config = Config {
+            base_url: self
+                .base_url
+                .ok_or(ApiError::MissingClientSetting("base_url"))?,
+            local_username: self
+                .local_username
+                .ok_or(ApiError::MissingClientSetting("local_username"))?,
+            full_username: self
+                .full_username
+                .ok_or(ApiError::MissingClientSetting("full_username"))?,
+            password: self.password,
+            token: self.token,
+            ..Default::default()
+        };
+
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => {
+                let mut http = reqwest::Client::builder();
+                if let Some(timeout) = self.timeout {
+                    http = http.timeout(timeout);
+                }
+                if let Some(connect_timeout) = self.connect_timeout {
+                    http = http.connect_timeout(connect_timeout);
+                }
+                Box::new(http.build()?)
+            }
+        };
+
+        Ok(MatrixClient {
+            config,
+            transport,
+            retry_policy: self.retry_policy,
+        })
+    }
+}
+
+/// An invite accepted by `MatrixClient::accept_invites`, with the outcome of joining its room.
+pub type InviteOutcome = (Invite, Result<(), ApiError>);
+
+/// A session with a single homeserver, owning its HTTP transport and access token.
+pub struct MatrixClient {
+    config: Config,
+    transport: Box<dyn Transport>,
+    retry_policy: RetryPolicy,
+}
+
+impl fmt::Debug for MatrixClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MatrixClient")
+            .field("config", &self.config)
+            .field("retry_policy", &self.retry_policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MatrixClient {
+    pub fn builder() -> MatrixClientBuilder {
+        MatrixClientBuilder::default()
+    }
+
+    /// Starts a builder populated with the connection settings of a config file.
+    pub fn builder_from_config(config: &Config) -> MatrixClientBuilder {
+        let mut builder = MatrixClient::builder()
+            .base_url(config.base_url.as_str())
+            .user(
+                config.local_username.as_str(),
+                config.full_username.as_str(),
+            );
+        if let Some(password) = &config.password {
+            builder = builder.password(password.as_str());
+        }
+        if let Some(token) = &config.token {
+            builder = builder.token(token.as_str());
+        }
+        builder
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.config.token.as_deref()
+    }
+
+    pub fn set_token(&mut self, token: impl Into<String>) {
+        self.config.token = Some(token.into());
+    }
+
+    /// Logs in with the configured password, keeping the new access token for later requests.
+    pub async fn login(&mut self) -> Result<String, ApiError> {
+        let token = self
+            .with_retry(|| api::login(&self.config, self.transport.as_ref()))
+            .await?;
+        self.set_token(token.as_str());
+        Ok(token)
+    }
+
+    /// Checks that the current access token is accepted by the homeserver.
+    pub async fn verify_token(&self) -> Result<String, ApiError> {
+        let token = self.token().ok_or(ApiError::MissingToken)?;
+        self.with_retry(|| api::verify_token(token, &self.config, self.transport.as_ref()))
+            .await
+    }
+
+    pub async fn display_name(&self, user_id: &str) -> Result<Option<String>, ApiError> {
+        self.with_retry(|| api::get_display_name(user_id, &self.config, self.transport.as_ref()))
+            .await
+    }
+
+    /// Lists the IDs of all rooms the client has joined.
+    pub async fn joined_rooms(&self) -> Result<Vec<String>, ApiError> {
+        self.with_retry(|| api::get_joined_rooms(&self.config, self.transport.as_ref()))
+            .await
+    }
+
+    pub async fn is_member(&self, room: &str) -> Result<bool, ApiError> {
+        self.with_retry(|| api::verify_in_room(room, &self.config, self.transport.as_ref()))
+            .await
+    }
+
+    pub async fn join(&self, room: &str) -> Result<(), ApiError> {
+        self.join_via(room, &[]).await
+    }
+
+    /// Joins a room the homeserver may not know yet, through the given servers.
+    pub async fn join_via(&self, room: &str, via: &[String]) -> Result<(), ApiError> {
+        self.with_retry(|| api::join_room(room, via, &self.config, self.transport.as_ref()))
+            .await
+    }
+
+    /// Resolves a `#alias:server` to the ID of its room, other rooms are returned as is.
+    pub async fn room_id(&self, room: &str) -> Result<String, ApiError> {
+        if !room.starts_with('#') {
+            return Ok(room.to_owned());
+        }
+        self.with_retry(|| api::resolve_room_alias(room, &self.config, self.transport.as_ref()))
+            .await
+    }
+
+    pub async fn invite(
+        &self,
+        room: &str,
+        user_id: &str,
+        reason: Option<&str>,
+    ) -> Result<(), ApiError> {
+        self.with_retry(|| {
+            api::invite(room, user_id, reason, &self.config, self.transport.as_ref())
+        })
+        .await
+    }
+
+    pub async fn kick(
+        &self,
+        room: &str,
+        user_id: &str,
+        reason: Option<&str>,
+    ) -> Result<(), ApiError> {
+        self.with_retry(|| api::kick(room, user_id, reason, &self.config, self.transport.as_ref()))
+            .await
+    }
+
+    pub async fn leave(&self, room: &str, reason: Option<&str>) -> Result<(), ApiError> {
+        self.with_retry(|| api::leave_room(room, reason, &self.config, self.transport.as_ref()))
+            .await
+    }
+
+    /// Forgets a room that was left, so it no longer shows up for the user.
+    pub async fn forget(&self, room: &str) -> Result<(), ApiError> {
+        self.with_retry(|| api::forget_room(room, &self.config, self.transport.as_ref()))
+            .await
+    }
+
+    /// Lists the rooms the client is invited to.
+    pub async fn invites(&self) -> Result<Vec<Invite>, ApiError> {
+        self.with_retry(|| api::get_invites(&self.config, self.transport.as_ref()))
+            .await
+    }
+
+    /// Joins every invited room whose inviter is allowed by the `invite_allowlist`
+    /// of the config, returning each allowed invite with the outcome of joining it.
+    ///
+    /// A failed join does not stop the remaining invites from being accepted.
+    pub async fn accept_invites(&self) -> Result<Vec<InviteOutcome>, ApiError> {
+        let mut outcomes = Vec::new();
+        for invite in self.invites().await? {
+            if self.config.allows_inviter(invite.inviter.as_deref()) {
+                let joined = self.join(&invite.room_id).await;
+                outcomes.push((invite, joined));
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// Creates a room, returning its ID.
+    ///
+    /// Not retried, as repeating the request could create a second room.
+    pub async fn create_room(&self, room: &NewRoom) -> Result<String, ApiError> {
+        api::create_room(room, &self.config, self.transport.as_ref()).await
+    }
+
+    /// Finds a direct chat with `user_id` that the client is still in, creating one if needed.
+    ///
+    /// Direct chats are tracked in the `m.direct` account data, so clients show them as such.
+    pub async fn direct_room(&self, user_id: &str) -> Result<String, ApiError> {
+        let mut direct_rooms = self
+            .with_retry(|| api::get_direct_rooms(&self.config, self.transport.as_ref()))
+            .await?;
+
+        if let Some(candidates) = direct_rooms.get(user_id).filter(|rooms| !rooms.is_empty()) {
+            let joined_rooms = self.joined_rooms().await?;
+            if let Some(room) = candidates.iter().find(|room| joined_rooms.contains(room)) {
+                return Ok(room.clone());
+            }
+        }
+
+        let room = self.create_room(&NewRoom::direct(user_id)).await?;
+        direct_rooms
+            .entry(user_id.to_owned())
+            .or_default()
+            .push(room.clone());
+        self.with_retry(|| {
+            api::set_direct_rooms(&direct_rooms, &self.config, self.transport.as_ref())
+        })
+        .await?;
+
+        Ok(room)
+    }
+
+    /// Sends a message, returning the ID of the created event.
+    pub async fn send(&self, room: &str, message: &Message) -> Result<SentEvent, ApiError> {
+        self.send_with_txn(room, |txn_id| async move {
+            api::send_message(
+                message,
+                room,
+                &txn_id,
+                &self.config,
+                self.transport.as_ref(),
+            )
+            .await
+        })
+        .await
+    }
+
+    /// Sends a message, joining the room through the `via` servers and sending again
+    /// if the homeserver refuses the first attempt because the client is not in the room.
+    /// `room` may also be an alias, which is resolved to its room ID first.
+    pub async fn send_joining(
+        &self,
+        room: &str,
+        via: &[String],
+        message: &Message,
+    ) -> Result<SentEvent, ApiError> {
+        let room_id = self.room_id(room).await?;
+        match self.send(&room_id, message).await {
+            Err(ApiError::Forbidden { .. }) => {
+                self.join_via(room, via).await?;
+                self.send(&room_id, message).await
+            }
+            sent_event => sent_event,
+        }
+    }
+
+    /// Sends a custom event, such as `com.example.deploy`, with arbitrary JSON content.
+    pub async fn send_event(
+        &self,
+        room: &str,
+        event_type: &str,
+        content: &serde_json::Value,
+    ) -> Result<SentEvent, ApiError> {
+        self.send_with_txn(room, |txn_id| async move {
+            api::send_event(
+                room,
+                event_type,
+                content,
+                &txn_id,
+                &self.config,
+                self.transport.as_ref(),
+            )
+            .await
+        })
+        .await
+    }
+
+    /// Sets a state event, such as `m.room.topic` or `m.room.pinned_events`, in a room.
+    pub async fn set_state(
+        &self,
+        room: &str,
+        event_type: &str,
+        state_key: &str,
+        content: &serde_json::Value,
+    ) -> Result<StateEvent, ApiError> {
+        let event_id = self
+            .with_retry(|| {
+                api::set_state(
+                    room,
+                    event_type,
+                    state_key,
+                    content,
+                    &self.config,
+                    self.transport.as_ref(),
+                )
+            })
+            .await?;
+
+        Ok(StateEvent {
+            room_id: room.to_owned(),
+            event_id,
+            event_type: event_type.to_owned(),
+            state_key: state_key.to_owned(),
+        })
+    }
+
+    pub async fn set_topic(&self, room: &str, topic: &str) -> Result<StateEvent, ApiError> {
+        self.set_state(room, "m.room.topic", "", &json!({ "topic": topic }))
+            .await
+    }
+
+    pub async fn set_name(&self, room: &str, name: &str) -> Result<StateEvent, ApiError> {
+        self.set_state(room, "m.room.name", "", &json!({ "name": name }))
+            .await
+    }
+
+    /// Replaces the content of a previously sent message.
+    pub async fn edit(
+        &self,
+        room: &str,
+        event_id: &str,
+        message: &Message,
+    ) -> Result<SentEvent, ApiError> {
+        self.send(room, &message.clone().replacing(event_id)).await
+    }
+
+    /// Removes the content of an event, returning the redaction event.
+    pub async fn redact(
+        &self,
+        room: &str,
+        event_id: &str,
+        reason: Option<&str>,
+    ) -> Result<SentEvent, ApiError> {
+        self.send_with_txn(room, |txn_id| async move {
+            api::redact(
+                room,
+                event_id,
+                reason,
+                &txn_id,
+                &self.config,
+                self.transport.as_ref(),
+            )
+            .await
+        })
+        .await
+    }
+
+    /// Annotates an event with `key`, typically an emoji, returning the reaction event.
+    pub async fn react(
+        &self,
+        room: &str,
+        event_id: &str,
+        key: &str,
+    ) -> Result<SentEvent, ApiError> {
+        self.send_with_txn(room, |txn_id| async move {
+            api::react(
+                room,
+                event_id,
+                key,
+                &txn_id,
+                &self.config,
+                self.transport.as_ref(),
+            )
+            .await
+        })
+        .await
+    }
+
+    pub async fn send_text(&self, room: &str, message: &str) -> Result<SentEvent, ApiError> {
+        self.send(room, &Message::text(message)).await
+    }
+
+    pub async fn send_html(
+        &self,
+        room: &str,
+        message: &str,
+        html: &str,
+    ) -> Result<SentEvent, ApiError> {
+        self.send(room, &Message::html(message, html)).await
+    }
+
+    pub async fn send_notice(&self, room: &str, message: &str) -> Result<SentEvent, ApiError> {
+        self.send(
+            room,
+            &Message::text(message).with_msgtype(MessageType::Notice),
+        )
+        .await
+    }
+
+    /// Retries reuse the same transaction ID, so the homeserver will not create duplicate events.
+    async fn send_with_txn<F, Fut>(&self, room: &str, mut request: F) -> Result<SentEvent, ApiError>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<String, ApiError>>,
+    {
+        let txn_id = api::new_txn_id();
+        let event_id = self.with_retry(|| request(txn_id.clone())).await?;
+
+        Ok(SentEvent {
+            room_id: room.to_owned(),
+            event_id,
+            txn_id,
+        })
+    }
+
+    async fn with_retry<T, F, Fut>(&self, mut request: F) -> Result<T, ApiError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ApiError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match request().await {
+                Err(e) if e.is_retryable() && attempt < self.retry_policy.max_retries => {
+                    tokio::time::sleep(self.retry_policy.backoff(attempt, &e)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use fake::{
+        faker::internet::en::{Password, Username},
+        faker::lorem::en::{Sentence, Word},
+        Fake,
+    };
+    use matches::assert_matches;
+    use reqwest::Method;
+
+    use crate::api::mock_server::{path_matcher, ANY_TXN_ID};
+    use crate::api::{ApiError, InMemoryTransport};
+
+    use super::{MatrixClient, RetryPolicy};
+
+    #[test]
+    fn test_build_without_base_url() {
+        let client_result = MatrixClient::builder()
+            .user(Username().fake::<String>(), Username().fake::<String>())
+            .build();
+
+        assert_matches!(
+            client_result.unwrap_err(),
+            ApiError::MissingClientSetting("base_url")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_text_retries_when_rate_limited() {
+        let mut mock_server = mockito::Server::new();
+
+        let base_url = format!("http://{}", mock_server.host_with_port());
+
+        let client = MatrixClient::builder()
+            .base_url(base_url.as_str())
+            .user(Username().fake::<String>(), Username().fake::<String>())
+            .token(Password(16..24).fake::<String>())
+            .retry_policy(RetryPolicy {
+                max_retries: 1,
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(50),
+            })
+            .build()
+            .unwrap();
+
+        let room: String = Word().fake();
+        let message: String = Sentence(1..2).fake();
+
+        let send_message_url = crate::config::build_send_message_url(&base_url, &room, ANY_TXN_ID);
+
+        let rate_limited_endpoint = mock_server
+            .mock("PUT", path_matcher(&base_url, &send_message_url))
+            .with_status(429)
+            .with_body(
+                r#"{"errcode": "M_LIMIT_EXCEEDED", "error": "Too many requests", "retry_after_ms": 1}"#,
+            )
+            .expect(1)
+            .create();
+        let success_endpoint = mock_server
+            .mock("PUT", path_matcher(&base_url, &send_message_url))
+            .with_status(200)
+            .with_body(r#"{"event_id": "$testevent:testmatrix"}"#)
+            .expect(1)
+            .create();
+
+        let func_result = client.send_text(room.as_str(), message.as_str()).await;
+
+        rate_limited_endpoint.assert();
+        success_endpoint.assert();
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+    }
+
+    #[tokio::test]
+    async fn test_join_with_custom_transport() {
+        let base_url = "http://localhost";
+        let room: String = Word().fake();
+
+        let transport = InMemoryTransport::new();
+        transport.respond(
+            Method::POST,
+            crate::config::build_join_room_url(base_url, &room, &[]),
+            200,
+            "{}",
+        );
+
+        let client = MatrixClient::builder()
+            .base_url(base_url)
+            .user(Username().fake::<String>(), Username().fake::<String>())
+            .token(Password(16..24).fake::<String>())
+            .transport(transport)
+            .build()
+            .unwrap();
+
+        let func_result = client.join(room.as_str()).await;
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+    }
+
+    #[tokio::test]
+    async fn test_send_joining_resolves_alias() {
+        let mut mock_server = mockito::Server::new();
+
+        let base_url = format!("http://{}", mock_server.host_with_port());
+        let alias = "#alerts:testmatrix";
+        let room_id = "!alerts:testmatrix";
+
+        let client = MatrixClient::builder()
+            .base_url(base_url.as_str())
+            .user(Username().fake::<String>(), Username().fake::<String>())
+            .token(Password(16..24).fake::<String>())
+            .build()
+            .unwrap();
+
+        let room_alias_url = crate::config::build_room_alias_url(&base_url, alias);
+        let join_room_url = crate::config::build_join_room_url(&base_url, alias, &[]);
+        let send_message_url =
+            crate::config::build_send_message_url(&base_url, room_id, ANY_TXN_ID);
+        let room_alias_endpoint = mock_server
+            .mock("GET", path_matcher(&base_url, &room_alias_url))
+            .with_status(200)
+            .with_body(r#"{"room_id": "!alerts:testmatrix", "servers": ["testmatrix"]}"#)
+            .create();
+        let forbidden_endpoint = mock_server
+            .mock("PUT", path_matcher(&base_url, &send_message_url))
+            .with_status(403)
+            .with_body(r#"{"errcode": "M_FORBIDDEN", "error": "User not in room"}"#)
+            .create();
+        let join_room_endpoint = mock_server
+            .mock("POST", path_matcher(&base_url, &join_room_url))
+            .with_status(200)
+            .with_body(r#"{"room_id": "!alerts:testmatrix"}"#)
+            .create();
+        let send_message_endpoint = mock_server
+            .mock("PUT", path_matcher(&base_url, &send_message_url))
+            .with_status(200)
+            .with_body(r#"{"event_id": "$testevent:testmatrix"}"#)
+            .create();
+
+        let func_result = client
+            .send_joining(alias, &[], &crate::api::Message::text("Disk full"))
+            .await;
+
+        room_alias_endpoint.assert();
+        forbidden_endpoint.assert();
+        join_room_endpoint.assert();
+        send_message_endpoint.assert();
+        assert!(func_result.is_ok(), "{:?}", func_result);
+    }
+
+    #[tokio::test]
+    async fn test_accept_invites_continues_after_failed_join() {
+        let base_url = "http://localhost";
+        let full_username = "@bot:testmatrix";
+        let invite = serde_json::json!({
+            "invite_state": {
+                "events": [{
+                    "type": "m.room.member",
+                    "state_key": full_username,
+                    "sender": "@admin:testmatrix",
+                    "content": { "membership": "invite" },
+                }],
+            },
+        });
+        let sync_body = serde_json::json!({
+            "next_batch": "s1",
+            "rooms": {
+                "invite": {
+                    "!banned:testmatrix": invite,
+                    "!open:testmatrix": invite,
+                },
+            },
+        });
+
+        let transport = InMemoryTransport::new();
+        transport
+            .respond(
+                Method::GET,
+                crate::config::build_invites_sync_url(base_url),
+                200,
+                sync_body.to_string(),
+            )
+            .respond(
+                Method::POST,
+                crate::config::build_join_room_url(base_url, "!banned:testmatrix", &[]),
+                403,
+                r#"{"errcode": "M_FORBIDDEN", "error": "You are banned from the room"}"#,
+            )
+            .respond(
+                Method::POST,
+                crate::config::build_join_room_url(base_url, "!open:testmatrix", &[]),
+                200,
+                "{}",
+            );
+
+        let client = MatrixClient::builder()
+            .base_url(base_url)
+            .user(Username().fake::<String>(), full_username)
+            .token(Password(16..24).fake::<String>())
+            .transport(transport)
+            .build()
+            .unwrap();
+
+        let outcomes = client.accept_invites().await.unwrap();
+
+        assert_eq!(outcomes.len(), 2);
+        for (invite, joined) in outcomes {
+            match invite.room_id.as_str() {
+                "!banned:testmatrix" => assert_matches!(joined, Err(ApiError::NotInvited { .. })),
+                _ => assert!(joined.is_ok(), "{:?}", joined),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_direct_room_created_and_tracked() {
+        let base_url = "http://localhost";
+        let full_username = "@bot:testmatrix";
+        let direct_rooms_url =
+            crate::config::build_account_data_url(base_url, full_username, "m.direct");
+
+        let transport = InMemoryTransport::new();
+        transport
+            .respond(
+                Method::GET,
+                direct_rooms_url.as_str(),
+                404,
+                r#"{"errcode": "M_NOT_FOUND", "error": "Account data not found"}"#,
+            )
+            .respond(
+                Method::POST,
+                crate::config::build_create_room_url(base_url),
+                200,
+                r#"{"room_id": "!dm:testmatrix"}"#,
+            )
+            .respond(Method::PUT, direct_rooms_url.as_str(), 200, "{}");
+
+        let client = MatrixClient::builder()
+            .base_url(base_url)
+            .user(Username().fake::<String>(), full_username)
+            .token(Password(16..24).fake::<String>())
+            .transport(transport)
+            .build()
+            .unwrap();
+
+        let func_result = client.direct_room("@alice:testmatrix").await;
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert_eq!(func_result.unwrap(), "!dm:testmatrix");
+    }
+
+    #[tokio::test]
+    async fn test_direct_room_reused() {
+        let base_url = "http://localhost";
+        let full_username = "@bot:testmatrix";
+
+        let transport = Arc::new(InMemoryTransport::new());
+        transport
+            .respond(
+                Method::GET,
+                crate::config::build_account_data_url(base_url, full_username, "m.direct"),
+                200,
+                r#"{"@alice:testmatrix": ["!left:testmatrix", "!old:testmatrix", "!dm:testmatrix"]}"#,
+            )
+            .respond(
+                Method::GET,
+                crate::config::build_joined_rooms_url(base_url),
+                200,
+                r#"{"joined_rooms": ["!dm:testmatrix"]}"#,
+            );
+
+        let client = MatrixClient::builder()
+            .base_url(base_url)
+            .user(Username().fake::<String>(), full_username)
+            .token(Password(16..24).fake::<String>())
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+
+        let func_result = client.direct_room("@alice:testmatrix").await;
+
+        assert!(func_result.is_ok(), "{:?}", func_result);
+        assert_eq!(func_result.unwrap(), "!dm:testmatrix");
+        assert_eq!(
+            transport
+                .requests()
+                .iter()
+                .filter(|request| request.url.ends_with("/joined_rooms"))
+                .count(),
+            1
+        );
+    }
+}
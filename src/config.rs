@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
-use std::{fs, io};
+use std::{collections::BTreeMap, fs, io};
 use thiserror::Error;
 
+use crate::api::{MessageType, MESSAGE_EVENT_TYPE};
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("IO error: {0}")]
@@ -12,13 +14,111 @@ pub enum ConfigError {
     TomlDeserialize(#[from] toml::de::Error),
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Default)]
 pub struct Config {
     pub base_url: String,
     pub local_username: String,
     pub full_username: String,
     pub password: Option<String>,
     pub token: Option<String>,
+    /// Message type used when `--msgtype` is not given, `text` if unset.
+    pub msgtype: Option<MessageType>,
+    /// Named templates, selectable with `--template-name` and includable from other templates.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub templates: BTreeMap<String, String>,
+    /// Users whose invites `invites --accept` accepts, any inviter if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invite_allowlist: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub server: ServerConfig,
+}
+
+/// Settings of `matrix-notify serve`, the `[server]` table of the config.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Default)]
+pub struct ServerConfig {
+    /// Address to listen on when `--listen` is not given.
+    pub listen: Option<String>,
+    /// Shared secret incoming requests must present as `Authorization: Bearer <secret>`,
+    /// overridden by the `MATRIX_NOTIFY_SECRET` environment variable.
+    pub secret: Option<String>,
+    /// Unix socket to listen on instead of `listen` when `--socket` is not given.
+    pub socket: Option<String>,
+    /// Permissions of the Unix socket, 0o660 if unset.
+    pub socket_mode: Option<u32>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub alertmanager: AlertmanagerConfig,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub forge: ForgeConfig,
+    /// Routes served at `/webhook/{name}`, by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub webhooks: BTreeMap<String, WebhookRoute>,
+}
+
+/// Routing of Alertmanager webhooks, the `[server.alertmanager]` table of the config.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Default)]
+pub struct AlertmanagerConfig {
+    /// Room each Alertmanager receiver is sent to.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub receivers: BTreeMap<String, String>,
+    /// Room for receivers missing from `receivers`, such alerts are rejected if unset.
+    pub room: Option<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub on_resolve: ResolveAction,
+}
+
+impl AlertmanagerConfig {
+    pub fn room_for(&self, receiver: &str) -> Option<&str> {
+        self.receivers
+            .get(receiver)
+            .or(self.room.as_ref())
+            .map(String::as_str)
+    }
+}
+
+/// A webhook mapping arbitrary JSON to a message, a `[server.webhooks.<name>]` table.
+///
+/// All fields are templates rendered with the fields of the JSON payload as variables.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Default)]
+pub struct WebhookRoute {
+    pub room: String,
+    pub template: String,
+    pub html_template: Option<String>,
+    /// Message type of the route, `msgtype` of the config if unset.
+    pub msgtype: Option<MessageType>,
+}
+
+/// Routing of GitHub, Gitea and GitLab webhooks, the `[server.forge]` table of the config.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Default)]
+pub struct ForgeConfig {
+    /// Room the events of each repository are sent to, by full name such as `owner/repo`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub repositories: BTreeMap<String, String>,
+    /// Room for repositories missing from `repositories`, their events are rejected if unset.
+    pub room: Option<String>,
+}
+
+impl ForgeConfig {
+    pub fn room_for(&self, repository: &str) -> Option<&str> {
+        self.repositories
+            .get(repository)
+            .or(self.room.as_ref())
+            .map(String::as_str)
+    }
+}
+
+/// How the message of a firing alert group is updated once it resolves.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ResolveAction {
+    /// Replace the message with the resolved alerts.
+    #[default]
+    Edit,
+    /// React to the message with ✅.
+    React,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
 }
 
 impl Config {
@@ -33,24 +133,83 @@ impl Config {
         Ok(())
     }
 
+    /// Whether an invite from `inviter` is allowed by `invite_allowlist`.
+    pub fn allows_inviter(&self, inviter: Option<&str>) -> bool {
+        self.invite_allowlist.is_empty()
+            || inviter
+                .is_some_and(|inviter| self.invite_allowlist.iter().any(|user| user == inviter))
+    }
+
     pub fn get_profile_url(&self) -> String {
         build_profile_url(self.base_url.as_str(), self.full_username.as_str())
     }
 
+    pub fn get_user_profile_url(&self, user_id: &str) -> String {
+        build_profile_url(self.base_url.as_str(), user_id)
+    }
+
     pub fn get_login_url(&self) -> String {
         build_login_url(self.base_url.as_str())
     }
 
-    pub fn get_room_members_url(&self, room: &str) -> String {
-        build_room_members_url(self.base_url.as_str(), room)
+    pub fn get_joined_rooms_url(&self) -> String {
+        build_joined_rooms_url(self.base_url.as_str())
+    }
+
+    pub fn get_room_alias_url(&self, alias: &str) -> String {
+        build_room_alias_url(self.base_url.as_str(), alias)
+    }
+
+    pub fn get_join_room_url(&self, room: &str, via: &[String]) -> String {
+        build_join_room_url(self.base_url.as_str(), room, via)
+    }
+
+    pub fn get_invite_url(&self, room: &str) -> String {
+        build_invite_url(self.base_url.as_str(), room)
+    }
+
+    pub fn get_kick_url(&self, room: &str) -> String {
+        build_kick_url(self.base_url.as_str(), room)
+    }
+
+    pub fn get_leave_room_url(&self, room: &str) -> String {
+        build_leave_room_url(self.base_url.as_str(), room)
+    }
+
+    pub fn get_forget_room_url(&self, room: &str) -> String {
+        build_forget_room_url(self.base_url.as_str(), room)
+    }
+
+    pub fn get_send_message_url(&self, room: &str, txn_id: &str) -> String {
+        build_send_message_url(self.base_url.as_str(), room, txn_id)
+    }
+
+    pub fn get_redact_url(&self, room: &str, event_id: &str, txn_id: &str) -> String {
+        build_redact_url(self.base_url.as_str(), room, event_id, txn_id)
+    }
+
+    pub fn get_room_state_url(&self, room: &str, event_type: &str, state_key: &str) -> String {
+        build_room_state_url(self.base_url.as_str(), room, event_type, state_key)
+    }
+
+    pub fn get_invites_sync_url(&self) -> String {
+        build_invites_sync_url(self.base_url.as_str())
+    }
+
+    pub fn get_create_room_url(&self) -> String {
+        build_create_room_url(self.base_url.as_str())
     }
 
-    pub fn get_join_room_url(&self, room: &str) -> String {
-        build_join_room_url(self.base_url.as_str(), room)
+    pub fn get_direct_rooms_url(&self) -> String {
+        build_account_data_url(
+            self.base_url.as_str(),
+            self.full_username.as_str(),
+            "m.direct",
+        )
     }
 
-    pub fn get_send_message_url(&self, room: &str) -> String {
-        build_send_message_url(self.base_url.as_str(), room)
+    pub fn get_send_event_url(&self, room: &str, event_type: &str, txn_id: &str) -> String {
+        build_send_event_url(self.base_url.as_str(), room, event_type, txn_id)
     }
 }
 
@@ -62,21 +221,143 @@ pub fn build_login_url(base_url: &str) -> String {
     format!("{}/_matrix/client/r0/login", base_url)
 }
 
-pub fn build_room_members_url(base_url: &str, room: &str) -> String {
+pub fn build_joined_rooms_url(base_url: &str) -> String {
+    format!("{}/_matrix/client/r0/joined_rooms", base_url)
+}
+
+pub fn build_room_alias_url(base_url: &str, alias: &str) -> String {
+    format!(
+        "{}/_matrix/client/r0/directory/room/{}",
+        base_url,
+        encode_url_component(alias),
+    )
+}
+
+/// Joins through the `via` servers followed by the server in the room ID or alias.
+pub fn build_join_room_url(base_url: &str, room: &str, via: &[String]) -> String {
+    let mut server_names: Vec<&str> = via.iter().map(String::as_str).collect();
+    if let Some((_, room_server)) = room.split_once(':') {
+        if !server_names.contains(&room_server) {
+            server_names.push(room_server);
+        }
+    }
+
+    let query: Vec<String> = server_names
+        .iter()
+        .map(|server_name| format!("server_name={}", encode_url_component(server_name)))
+        .collect();
+    format!(
+        "{}/_matrix/client/r0/join/{}?{}",
+        base_url,
+        encode_url_component(room),
+        query.join("&"),
+    )
+}
+
+pub fn build_invite_url(base_url: &str, room: &str) -> String {
+    format!(
+        "{}/_matrix/client/r0/rooms/{}/invite",
+        base_url,
+        encode_url_component(room),
+    )
+}
+
+pub fn build_kick_url(base_url: &str, room: &str) -> String {
+    format!(
+        "{}/_matrix/client/r0/rooms/{}/kick",
+        base_url,
+        encode_url_component(room),
+    )
+}
+
+pub fn build_leave_room_url(base_url: &str, room: &str) -> String {
     format!(
-        "{}/_matrix/client/r0/rooms/{}/joined_members",
-        base_url, room,
+        "{}/_matrix/client/r0/rooms/{}/leave",
+        base_url,
+        encode_url_component(room),
     )
 }
 
-pub fn build_join_room_url(base_url: &str, room: &str) -> String {
-    format!("{}/_matrix/client/r0/rooms/{}/join", base_url, room,)
+pub fn build_forget_room_url(base_url: &str, room: &str) -> String {
+    format!(
+        "{}/_matrix/client/r0/rooms/{}/forget",
+        base_url,
+        encode_url_component(room),
+    )
 }
 
-pub fn build_send_message_url(base_url: &str, room: &str) -> String {
+pub fn build_send_message_url(base_url: &str, room: &str, txn_id: &str) -> String {
+    build_send_event_url(base_url, room, MESSAGE_EVENT_TYPE, txn_id)
+}
+
+pub fn build_redact_url(base_url: &str, room: &str, event_id: &str, txn_id: &str) -> String {
     format!(
-        "{}/_matrix/client/r0/rooms/{}/send/m.room.message",
-        base_url, room,
+        "{}/_matrix/client/r0/rooms/{}/redact/{}/{}",
+        base_url,
+        encode_url_component(room),
+        encode_url_component(event_id),
+        txn_id,
+    )
+}
+
+/// Percent-encodes an identifier for use as a single url path segment or query value,
+/// as event IDs in older room versions and room aliases contain reserved characters.
+pub fn encode_url_component(component: &str) -> String {
+    let mut encoded = String::with_capacity(component.len());
+    for byte in component.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+pub fn build_send_event_url(base_url: &str, room: &str, event_type: &str, txn_id: &str) -> String {
+    format!(
+        "{}/_matrix/client/r0/rooms/{}/send/{}/{}",
+        base_url,
+        encode_url_component(room),
+        event_type,
+        txn_id,
+    )
+}
+
+pub fn build_room_state_url(
+    base_url: &str,
+    room: &str,
+    event_type: &str,
+    state_key: &str,
+) -> String {
+    format!(
+        "{}/_matrix/client/r0/rooms/{}/state/{}/{}",
+        base_url,
+        encode_url_component(room),
+        event_type,
+        encode_url_component(state_key),
+    )
+}
+
+/// A `/sync` returning immediately with room invites, but no timelines, presence or account data.
+pub fn build_invites_sync_url(base_url: &str) -> String {
+    let filter = r#"{"room":{"timeline":{"limit":0},"state":{"types":[]},"ephemeral":{"types":[]},"account_data":{"types":[]}},"presence":{"types":[]},"account_data":{"types":[]}}"#;
+    format!(
+        "{}/_matrix/client/r0/sync?timeout=0&filter={}",
+        base_url,
+        encode_url_component(filter),
+    )
+}
+
+pub fn build_create_room_url(base_url: &str) -> String {
+    format!("{}/_matrix/client/r0/createRoom", base_url)
+}
+
+pub fn build_account_data_url(base_url: &str, username: &str, data_type: &str) -> String {
+    format!(
+        "{}/_matrix/client/r0/user/{}/account_data/{}",
+        base_url, username, data_type,
     )
 }
 
@@ -86,7 +367,8 @@ mod tests {
     use std::{fs, io::Write};
     use tempfile::NamedTempFile;
 
-    use crate::config::{Config, ConfigError};
+    use crate::api::MessageType;
+    use crate::config::{Config, ConfigError, ResolveAction};
 
     const FULL_CONFIG_CONTENTS: &str = r#"
 base_url = "https://example.org"
@@ -108,6 +390,7 @@ token = "access_token from previous api calls"
             full_username: "@matrix-bot:example.org".to_string(),
             password: Some("Plaintext password".to_string()),
             token: Some("access_token from previous api calls".to_string()),
+            ..Default::default()
         };
 
         assert_eq!(loaded_config, expected_config);
@@ -133,6 +416,7 @@ password = "Plaintext password"
             full_username: "@matrix-bot:example.org".to_string(),
             password: Some("Plaintext password".to_string()),
             token: None,
+            ..Default::default()
         };
 
         assert_eq!(loaded_config, expected_config);
@@ -158,11 +442,112 @@ token = "access_token from previous api calls"
             full_username: "@matrix-bot:example.org".to_string(),
             password: None,
             token: Some("access_token from previous api calls".to_string()),
+            ..Default::default()
         };
 
         assert_eq!(loaded_config, expected_config);
     }
 
+    const NOTICE_CONFIG_CONTENTS: &str = r#"
+base_url = "https://example.org"
+local_username = "matrix-bot"
+full_username = "@matrix-bot:example.org"
+token = "access_token from previous api calls"
+msgtype = "notice"
+
+invite_allowlist = ["@admin:example.org"]
+
+[templates]
+alert = "{{ severity }}: {{ summary }}"
+"#;
+    #[tokio::test]
+    async fn test_msgtype_config_load() {
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temporary file");
+        write!(temp_file, "{}", NOTICE_CONFIG_CONTENTS).expect("Failed to write to temporary file");
+
+        let loaded_config = Config::load(temp_file.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(loaded_config.msgtype, Some(MessageType::Notice));
+        assert_eq!(
+            loaded_config.templates.get("alert").map(String::as_str),
+            Some("{{ severity }}: {{ summary }}")
+        );
+        assert!(loaded_config.allows_inviter(Some("@admin:example.org")));
+        assert!(!loaded_config.allows_inviter(Some("@mallory:example.org")));
+        assert!(!loaded_config.allows_inviter(None));
+    }
+
+    const ALERTMANAGER_CONFIG_CONTENTS: &str = r#"
+base_url = "https://example.org"
+local_username = "matrix-bot"
+full_username = "@matrix-bot:example.org"
+
+[server.alertmanager]
+room = "!alerts:example.org"
+on_resolve = "react"
+
+[server.alertmanager.receivers]
+database = "!dba:example.org"
+"#;
+    #[tokio::test]
+    async fn test_alertmanager_config_load() {
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temporary file");
+        write!(temp_file, "{}", ALERTMANAGER_CONFIG_CONTENTS)
+            .expect("Failed to write to temporary file");
+
+        let loaded_config = Config::load(temp_file.path().to_str().unwrap()).unwrap();
+        let alertmanager = &loaded_config.server.alertmanager;
+
+        assert_eq!(alertmanager.on_resolve, ResolveAction::React);
+        assert_eq!(alertmanager.room_for("database"), Some("!dba:example.org"));
+        assert_eq!(alertmanager.room_for("web"), Some("!alerts:example.org"));
+    }
+
+    const FORGE_CONFIG_CONTENTS: &str = r#"
+base_url = "https://example.org"
+local_username = "matrix-bot"
+full_username = "@matrix-bot:example.org"
+
+[server.forge.repositories]
+"owner/repo" = "!dev:example.org"
+"#;
+    #[tokio::test]
+    async fn test_forge_config_load() {
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temporary file");
+        write!(temp_file, "{}", FORGE_CONFIG_CONTENTS).expect("Failed to write to temporary file");
+
+        let loaded_config = Config::load(temp_file.path().to_str().unwrap()).unwrap();
+        let forge = &loaded_config.server.forge;
+
+        assert_eq!(forge.room_for("owner/repo"), Some("!dev:example.org"));
+        assert_eq!(forge.room_for("owner/other"), None);
+    }
+
+    const WEBHOOKS_CONFIG_CONTENTS: &str = r#"
+base_url = "https://example.org"
+local_username = "matrix-bot"
+full_username = "@matrix-bot:example.org"
+
+[server.webhooks.grafana]
+room = "!ops:example.org"
+template = "{{ title }}: {{ message }}"
+msgtype = "notice"
+"#;
+    #[tokio::test]
+    async fn test_webhooks_config_load() {
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temporary file");
+        write!(temp_file, "{}", WEBHOOKS_CONFIG_CONTENTS)
+            .expect("Failed to write to temporary file");
+
+        let loaded_config = Config::load(temp_file.path().to_str().unwrap()).unwrap();
+        let grafana = &loaded_config.server.webhooks["grafana"];
+
+        assert_eq!(grafana.room, "!ops:example.org");
+        assert_eq!(grafana.template, "{{ title }}: {{ message }}");
+        assert_eq!(grafana.html_template, None);
+        assert_eq!(grafana.msgtype, Some(MessageType::Notice));
+    }
+
     const NO_BASE_URL_CONFIG_CONTENTS: &str = r#"
 local_username = "matrix-bot"
 full_username = "@matrix-bot:example.org"
@@ -227,6 +612,7 @@ token = "access_token from previous api calls"
             full_username: "@matrix-bot:example.org".to_string(),
             password: Some("Plaintext password".to_string()),
             token: Some("access_token from previous api calls".to_string()),
+            ..Default::default()
         };
         let save_result = config.save(temp_file.path().to_str().unwrap());
         assert!(save_result.is_ok());
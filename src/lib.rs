@@ -1,2 +1,10 @@
 pub mod api;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod client;
 pub mod config;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod template;
+
+pub use client::MatrixClient;
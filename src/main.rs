@@ -1,18 +1,34 @@
 #![forbid(unsafe_code)]
 
+use std::env;
 use std::fs;
+use std::io;
+#[cfg(all(feature = "server", unix))]
+use std::path::Path;
+use std::process;
 
 use clap::arg;
 use clap::crate_name;
 use clap::crate_version;
+use clap::Arg;
+use clap::ArgAction;
+use clap::ArgMatches;
 use clap::Command;
-use matrix_notify::api::join_room;
-use matrix_notify::api::login;
-use matrix_notify::api::send_message;
-use matrix_notify::api::verify_in_room;
-use matrix_notify::api::verify_token;
 use matrix_notify::api::ApiError;
+use matrix_notify::api::Message;
+use matrix_notify::api::MessageType;
+use matrix_notify::api::NewRoom;
+use matrix_notify::api::RoomPreset;
+use matrix_notify::api::MESSAGE_EVENT_TYPE;
 use matrix_notify::config::Config;
+#[cfg(feature = "server")]
+use matrix_notify::server;
+use matrix_notify::template;
+use matrix_notify::MatrixClient;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
 
 const CONFIG_FILE: &str = "matrix-notify.toml";
 
@@ -30,6 +46,7 @@ fn perform_generate() -> Result<(), ApiError> {
             "access_token from previous api calls, remove to populate via password driven login"
                 .to_owned(),
         ),
+        ..Default::default()
     };
     config.save(CONFIG_FILE)?;
     println!("An example config has been generated, see {}", CONFIG_FILE);
@@ -37,56 +54,599 @@ fn perform_generate() -> Result<(), ApiError> {
     Ok(())
 }
 
-async fn perform_send_message(room: &str, message: &str) -> Result<(), ApiError> {
+async fn connect() -> Result<(Config, MatrixClient), ApiError> {
     let mut config = Config::load(CONFIG_FILE)?;
-    let client = reqwest::Client::new();
+    let mut client = MatrixClient::builder_from_config(&config).build()?;
 
-    let valid_token = get_token(&config, &client).await?;
+    let valid_token = get_token(&mut client).await?;
     config.token = Some(valid_token);
     config.save(CONFIG_FILE)?;
 
-    if !verify_in_room(room, &config, &client).await? {
-        join_room(room, &config, &client).await?
+    Ok((config, client))
+}
+
+fn required<'a>(m: &'a ArgMatches, id: &str, name: &'static str) -> Result<&'a str, ApiError> {
+    m.get_one::<String>(id)
+        .map(String::as_str)
+        .ok_or(ApiError::MissingArgument(name))
+}
+
+fn uses_template(m: &ArgMatches) -> bool {
+    m.contains_id("template") || m.contains_id("template-name")
+}
+
+/// Collects template variables, `--var` overriding stdin JSON overriding the `env` namespace.
+fn template_context(m: &ArgMatches) -> Result<Map<String, Value>, ApiError> {
+    let mut context = Map::new();
+    context.insert(
+        "env".to_owned(),
+        Value::Object(env::vars().map(|(k, v)| (k, Value::String(v))).collect()),
+    );
+
+    if m.get_flag("stdin") {
+        match serde_json::from_reader(io::stdin().lock())? {
+            Value::Object(vars) => context.extend(vars),
+            _ => return Err(ApiError::InvalidTemplateInput),
+        }
+    }
+
+    for var in m.get_many::<String>("var").unwrap_or_default() {
+        let (key, value) = var
+            .split_once('=')
+            .ok_or_else(|| ApiError::InvalidTemplateVariable(var.to_owned()))?;
+        context.insert(key.to_owned(), Value::String(value.to_owned()));
+    }
+
+    Ok(context)
+}
+
+fn message_body(m: &ArgMatches, config: &Config) -> Result<String, ApiError> {
+    if let Some(path) = m.get_one::<String>("template") {
+        let source = fs::read_to_string(path).map_err(|source| ApiError::ReadFile {
+            path: path.to_owned(),
+            source,
+        })?;
+        return template::render(&source, &config.templates, template_context(m)?);
+    }
+    if let Some(name) = m.get_one::<String>("template-name") {
+        return template::render_named(name, &config.templates, template_context(m)?);
+    }
+    required(m, "message", "MESSAGE").map(str::to_owned)
+}
+
+fn build_message(m: &ArgMatches, config: &Config) -> Result<Message, ApiError> {
+    let body = message_body(m, config)?;
+    let mut message = match m.get_one::<String>("html") {
+        Some(html) => Message::html(&body, html),
+        None => Message::text(&body),
+    };
+
+    let msgtype = m
+        .get_one::<String>("msgtype")
+        .map(|msgtype| msgtype.parse::<MessageType>())
+        .transpose()?;
+    message = message.with_msgtype(msgtype.or(config.msgtype).unwrap_or_default());
+
+    Ok(message)
+}
+
+async fn add_mentions(m: &ArgMatches, client: &MatrixClient, mut message: Message) -> Message {
+    if m.get_flag("mention-room") {
+        message = message.mentioning_room();
+    }
+    for user_id in m.get_many::<String>("mention").unwrap_or_default() {
+        let display_name = match client.display_name(user_id).await {
+            Ok(display_name) => display_name,
+            Err(e) => {
+                eprintln!("Failed to resolve display name of {}: {}", user_id, e);
+                None
+            }
+        };
+        message = message.mentioning(user_id, display_name);
+    }
+    message
+}
+
+async fn perform_send_message(m: &ArgMatches) -> Result<(), ApiError> {
+    let to = m.get_one::<String>("to");
+    if to.is_none() {
+        required(m, "room", "ROOM_ID")?;
+    }
+    if !uses_template(m) {
+        required(m, "message", "MESSAGE")?;
+    }
+    let (config, client) = connect().await?;
+    let via: Vec<String> = m
+        .get_many::<String>("via")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+
+    let room = match to {
+        Some(user_id) => client.direct_room(user_id).await?,
+        None => required(m, "room", "ROOM_ID")?.to_owned(),
+    };
+
+    let mut message = build_message(m, &config)?;
+    message = add_mentions(m, &client, message).await;
+    if let Some(reply_to) = m.get_one::<String>("reply-to") {
+        message = message.in_reply_to(reply_to);
+    }
+    if let Some(thread) = m.get_one::<String>("thread") {
+        message = message.in_thread(thread);
+    }
+
+    let sent_event = client.send_joining(&room, &via, &message).await?;
+    print_event(&sent_event, &sent_event.event_id, m)
+}
+
+async fn perform_create_room(m: &ArgMatches) -> Result<(), ApiError> {
+    let mut room = NewRoom::default();
+    if let Some(name) = m.get_one::<String>("name") {
+        room = room.named(name);
+    }
+    if let Some(topic) = m.get_one::<String>("topic") {
+        room = room.with_topic(topic);
+    }
+    if let Some(preset) = m.get_one::<String>("preset") {
+        room = room.with_preset(preset.parse::<RoomPreset>()?);
+    }
+    for user_id in m.get_many::<String>("invite").unwrap_or_default() {
+        room = room.inviting(user_id);
+    }
+    if let Some(alias) = m.get_one::<String>("alias") {
+        room = room.with_alias(alias);
+    }
+    let (_, client) = connect().await?;
+
+    let room_id = client.create_room(&room).await?;
+    print_event(&json!({ "room_id": room_id }), &room_id, m)
+}
+
+async fn perform_invite(m: &ArgMatches) -> Result<(), ApiError> {
+    let room = required(m, "room", "ROOM_ID")?;
+    let user_id = required(m, "user", "USER_ID")?;
+    let (_, client) = connect().await?;
+
+    let reason = m.get_one::<String>("reason").map(String::as_str);
+
+    client.invite(room, user_id, reason).await
+}
+
+async fn perform_kick(m: &ArgMatches) -> Result<(), ApiError> {
+    let room = required(m, "room", "ROOM_ID")?;
+    let user_id = required(m, "user", "USER_ID")?;
+    let (_, client) = connect().await?;
+
+    let reason = m.get_one::<String>("reason").map(String::as_str);
+
+    client.kick(room, user_id, reason).await
+}
+
+async fn perform_leave(m: &ArgMatches) -> Result<(), ApiError> {
+    let room = required(m, "room", "ROOM_ID")?;
+    let (_, client) = connect().await?;
+
+    let reason = m.get_one::<String>("reason").map(String::as_str);
+
+    client.leave(room, reason).await
+}
+
+async fn perform_forget(m: &ArgMatches) -> Result<(), ApiError> {
+    let room = required(m, "room", "ROOM_ID")?;
+    let (_, client) = connect().await?;
+
+    client.forget(room).await
+}
+
+async fn perform_invites(m: &ArgMatches) -> Result<(), ApiError> {
+    let (config, client) = connect().await?;
+
+    let mut failed = 0;
+    let invites = if m.get_flag("accept") {
+        let outcomes = client.accept_invites().await?;
+        if outcomes.is_empty() && !config.invite_allowlist.is_empty() {
+            eprintln!("No pending invites from users in the invite_allowlist");
+        }
+        let mut accepted = Vec::new();
+        for (invite, joined) in outcomes {
+            match joined {
+                Ok(()) => accepted.push(invite),
+                Err(e) => {
+                    eprintln!("Failed to accept the invite to {}: {}", invite.room_id, e);
+                    failed += 1;
+                }
+            }
+        }
+        accepted
+    } else {
+        client.invites().await?
+    };
+
+    match m.get_one::<String>("output").map(String::as_str) {
+        Some("json") => println!("{}", serde_json::to_string(&invites)?),
+        _ => {
+            for invite in invites {
+                println!(
+                    "{}\t{}\t{}",
+                    invite.room_id,
+                    invite.inviter.as_deref().unwrap_or("-"),
+                    invite.room_name.as_deref().unwrap_or("-"),
+                );
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(ApiError::InvitesFailed(failed));
     }
+    Ok(())
+}
+
+async fn perform_edit(m: &ArgMatches) -> Result<(), ApiError> {
+    let room = required(m, "room", "ROOM_ID")?;
+    let event_id = required(m, "event", "EVENT_ID")?;
+    let (config, client) = connect().await?;
+
+    let message = build_message(m, &config)?;
+    let message = add_mentions(m, &client, message).await;
 
-    if let Err(e) = send_message(message, room, &config, &client).await {
-        eprintln!("Failed to send message: {}", e);
+    let sent_event = client.edit(room, event_id, &message).await?;
+    print_event(&sent_event, &sent_event.event_id, m)
+}
+
+async fn perform_send_event(m: &ArgMatches) -> Result<(), ApiError> {
+    let room = required(m, "room", "ROOM_ID")?;
+    let event_type = required(m, "type", "TYPE")?;
+    let content_path = required(m, "content-json", "FILE")?;
+    let content_json = if content_path == "-" {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(content_path)
+    }
+    .map_err(|source| ApiError::ReadFile {
+        path: content_path.to_owned(),
+        source,
+    })?;
+    let content: Value = serde_json::from_str(&content_json)?;
+    let (_, client) = connect().await?;
+
+    let sent_event = client.send_event(room, event_type, &content).await?;
+    print_event(&sent_event, &sent_event.event_id, m)
+}
+
+async fn perform_set_state(m: &ArgMatches) -> Result<(), ApiError> {
+    let room = required(m, "room", "ROOM_ID")?;
+    let event_type = required(m, "type", "TYPE")?;
+    let state_key = required(m, "key", "STATE_KEY")?;
+    let content: Value = serde_json::from_str(required(m, "content", "CONTENT")?)?;
+    let (_, client) = connect().await?;
+
+    let state_event = client
+        .set_state(room, event_type, state_key, &content)
+        .await?;
+    print_event(&state_event, &state_event.event_id, m)
+}
+
+async fn perform_topic(m: &ArgMatches) -> Result<(), ApiError> {
+    let room = required(m, "room", "ROOM_ID")?;
+    let topic = required(m, "topic", "TOPIC")?;
+    let (_, client) = connect().await?;
+
+    let state_event = client.set_topic(room, topic).await?;
+    print_event(&state_event, &state_event.event_id, m)
+}
+
+async fn perform_rename(m: &ArgMatches) -> Result<(), ApiError> {
+    let room = required(m, "room", "ROOM_ID")?;
+    let name = required(m, "name", "NAME")?;
+    let (_, client) = connect().await?;
+
+    let state_event = client.set_name(room, name).await?;
+    print_event(&state_event, &state_event.event_id, m)
+}
+
+async fn perform_redact(m: &ArgMatches) -> Result<(), ApiError> {
+    let room = required(m, "room", "ROOM_ID")?;
+    let event_id = required(m, "event", "EVENT_ID")?;
+    let (_, client) = connect().await?;
+
+    let reason = m.get_one::<String>("reason").map(String::as_str);
+
+    let sent_event = client.redact(room, event_id, reason).await?;
+    print_event(&sent_event, &sent_event.event_id, m)
+}
+
+async fn perform_react(m: &ArgMatches) -> Result<(), ApiError> {
+    let room = required(m, "room", "ROOM_ID")?;
+    let event_id = required(m, "event", "EVENT_ID")?;
+    let key = required(m, "key", "KEY")?;
+    let (_, client) = connect().await?;
+
+    let sent_event = client.react(room, event_id, key).await?;
+    print_event(&sent_event, &sent_event.event_id, m)
+}
+
+#[cfg(feature = "server")]
+async fn perform_serve(m: &ArgMatches) -> Result<(), ApiError> {
+    let (config, client) = connect().await?;
+
+    // An explicit --listen wins over server.socket, as --socket does over server.listen.
+    #[cfg(unix)]
+    if let Some(socket) = m.get_one::<String>("socket").cloned().or_else(|| {
+        config
+            .server
+            .socket
+            .clone()
+            .filter(|_| m.get_one::<String>("listen").is_none())
+    }) {
+        let mode = config
+            .server
+            .socket_mode
+            .unwrap_or(server::socket::DEFAULT_SOCKET_MODE);
+        let listener = server::socket::bind(Path::new(&socket), mode)?;
+
+        eprintln!("Listening on {}", socket);
+        return server::socket::serve(listener, socket.into(), client, config).await;
+    }
+
+    let secret = env::var("MATRIX_NOTIFY_SECRET")
+        .ok()
+        .or_else(|| config.server.secret.clone())
+        .filter(|secret| !secret.trim().is_empty())
+        .ok_or(ApiError::MissingArgument(
+            "MATRIX_NOTIFY_SECRET or server.secret",
+        ))?;
+    let listen = m
+        .get_one::<String>("listen")
+        .cloned()
+        .or_else(|| config.server.listen.clone())
+        .unwrap_or_else(|| server::DEFAULT_LISTEN_ADDRESS.to_owned());
+
+    eprintln!("Listening on {}", listen);
+    server::serve(&listen, server::router(client, config, secret)).await
+}
+
+fn print_event(event: &impl Serialize, event_id: &str, m: &ArgMatches) -> Result<(), ApiError> {
+    match m.get_one::<String>("output").map(String::as_str) {
+        Some("json") => println!("{}", serde_json::to_string(event)?),
+        _ => println!("{}", event_id),
     }
 
     Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), ApiError> {
-    let m = Command::new(crate_name!())
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn room_arg() -> Arg {
+    arg!(-r --room <ROOM_ID> "Room ID, typically in the format !roomid:matrix.org")
+}
+
+fn html_arg() -> Arg {
+    arg!(--html <HTML> "HTML formatted version of the message")
+}
+
+fn mention_args() -> [Arg; 2] {
+    [
+        arg!(--mention <USER_ID> "User to ping, may be repeated").action(ArgAction::Append),
+        arg!(--"mention-room" "Ping everyone in the room"),
+    ]
+}
+
+fn template_args() -> [Arg; 4] {
+    [
+        arg!(--template <FILE> "Template file rendered into the message text")
+            .conflicts_with_all(["message", "template-name"]),
+        arg!(--"template-name" <NAME> "Name of a template from the config rendered into the message text")
+            .conflicts_with("message"),
+        arg!(--var <KEY_VALUE> "Template variable in the format key=value, may be repeated")
+            .action(ArgAction::Append),
+        arg!(--stdin "Read template variables as a JSON object from stdin"),
+    ]
+}
+
+fn msgtype_arg() -> Arg {
+    arg!(--msgtype <MSGTYPE> "Message type, defaults to the msgtype in the config or text")
+        .value_parser(["text", "notice", "emote"])
+}
+
+async fn run() -> Result<(), ApiError> {
+    let command = Command::new(crate_name!())
         .version(crate_version!())
         .about("A command line tool for sending messages to a matrix chatroom")
-        .arg(arg!(-r --room <ROOM_ID> "Room ID, typically in the format !roomid:matrix.org"))
+        .arg(room_arg())
+        .arg(
+            arg!(--via <SERVER> "Server to join the room through if the homeserver does not know it, may be repeated")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--to <USER_ID> "User to message directly, finding or creating a direct chat")
+                .conflicts_with("room"),
+        )
         .arg(arg!(-m --message <MESSAGE> "Text to be sent"))
+        .arg(html_arg())
+        .arg(msgtype_arg())
+        .args(mention_args())
+        .args(template_args())
+        .arg(arg!(--"reply-to" <EVENT_ID> "Event ID of the message to reply to"))
+        .arg(arg!(--thread <EVENT_ID> "Event ID of the thread root to post the message under"))
+        .arg(
+            arg!(--output <FORMAT> "Print the sent event ID as text, or room_id, event_id and txn_id as json")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .global(true),
+        )
         .subcommand(Command::new("generate").about("Generates an example config file"))
-        .get_matches();
-    if m.subcommand_matches("generate").is_some() {
-        perform_generate()
-    } else {
-        let room = m
-            .get_one::<String>("room")
-            .expect("ROOM_ID must be provided, please see --help");
-        let message = m
-            .get_one::<String>("message")
-            .expect("MESSAGE must be provided, please see --help");
-        perform_send_message(room.as_str(), message.as_str()).await
+        .subcommand(
+            Command::new("edit")
+                .about("Replaces the content of a previously sent message")
+                .arg(room_arg().required(true))
+                .arg(arg!(-e --event <EVENT_ID> "Event ID of the message to edit").required(true))
+                .arg(
+                    arg!(-m --message <MESSAGE> "New text of the message")
+                        .required_unless_present_any(["template", "template-name"]),
+                )
+                .arg(html_arg())
+                .arg(msgtype_arg())
+                .args(mention_args())
+                .args(template_args()),
+        )
+        .subcommand(
+            Command::new("send-event")
+                .about("Sends a custom event with arbitrary JSON content")
+                .arg(room_arg().required(true))
+                .arg(
+                    arg!(-t --type <TYPE> "Event type, such as com.example.deploy")
+                        .default_value(MESSAGE_EVENT_TYPE),
+                )
+                .arg(
+                    arg!(--"content-json" <FILE> "File containing the event content as JSON, - for stdin")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("state")
+                .about("Manages room state events")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("set")
+                        .about("Sets a state event, such as m.room.topic or m.room.pinned_events")
+                        .arg(room_arg().required(true))
+                        .arg(arg!(-t --type <TYPE> "State event type").required(true))
+                        .arg(
+                            arg!(-k --key <STATE_KEY> "State key, empty for most state types")
+                                .default_value(""),
+                        )
+                        .arg(arg!(--content <JSON> "Content of the state event as JSON").required(true)),
+                ),
+        )
+        .subcommand(
+            Command::new("room")
+                .about("Manages rooms")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("create")
+                        .about("Creates a room and prints its ID")
+                        .arg(arg!(--name <NAME> "Name of the room"))
+                        .arg(arg!(--topic <TOPIC> "Topic of the room"))
+                        .arg(
+                            arg!(--preset <PRESET> "Join rules and permissions of the room")
+                                .value_parser(["private_chat", "trusted_private_chat", "public_chat"]),
+                        )
+                        .arg(
+                            arg!(--invite <USER_ID> "User to invite, may be repeated")
+                                .action(ArgAction::Append),
+                        )
+                        .arg(arg!(--alias <ALIAS> "Local part of the room alias, e.g. alerts for #alerts:example.org")),
+                )
+                .subcommand(
+                    Command::new("invite")
+                        .about("Invites a user into a room")
+                        .arg(room_arg().required(true))
+                        .arg(arg!(-u --user <USER_ID> "User to invite").required(true))
+                        .arg(arg!(--reason <REASON> "Reason for the invite, visible to the user")),
+                )
+                .subcommand(
+                    Command::new("kick")
+                        .about("Removes a user from a room")
+                        .arg(room_arg().required(true))
+                        .arg(arg!(-u --user <USER_ID> "User to kick").required(true))
+                        .arg(arg!(--reason <REASON> "Reason for the kick, visible to room members")),
+                )
+                .subcommand(
+                    Command::new("leave")
+                        .about("Leaves a room, or rejects an invite to it")
+                        .arg(room_arg().required(true))
+                        .arg(arg!(--reason <REASON> "Reason for leaving, visible to room members")),
+                )
+                .subcommand(
+                    Command::new("forget")
+                        .about("Forgets a room that was left, removing it from the room list")
+                        .arg(room_arg().required(true)),
+                ),
+        )
+        .subcommand(
+            Command::new("invites")
+                .about("Lists pending invites as room ID, inviter and room name")
+                .arg(arg!(--accept "Joins the invited rooms, limited to inviters in the invite_allowlist of the config if set")),
+        )
+        .subcommand(
+            Command::new("topic")
+                .about("Sets the topic of a room")
+                .arg(room_arg().required(true))
+                .arg(arg!(<TOPIC> "New topic of the room").id("topic")),
+        )
+        .subcommand(
+            Command::new("rename")
+                .about("Sets the name of a room")
+                .arg(room_arg().required(true))
+                .arg(arg!(<NAME> "New name of the room").id("name")),
+        )
+        .subcommand(
+            Command::new("redact")
+                .about("Removes the content of a previously sent event")
+                .arg(room_arg().required(true))
+                .arg(arg!(-e --event <EVENT_ID> "Event ID of the event to redact").required(true))
+                .arg(arg!(--reason <REASON> "Reason for the redaction, visible to room members")),
+        )
+        .subcommand(
+            Command::new("react")
+                .about("Reacts to a previously sent event")
+                .arg(room_arg().required(true))
+                .arg(arg!(-e --event <EVENT_ID> "Event ID of the event to react to").required(true))
+                .arg(arg!(-k --key <KEY> "Reaction, typically an emoji such as ✅").required(true)),
+        );
+    #[cfg(feature = "server")]
+    let serve = Command::new("serve")
+        .about("Relays webhooks posted to /send/{room} to Matrix, authenticated by server.secret")
+        .arg(arg!(--listen <ADDRESS> "Address to listen on, defaults to server.listen or 127.0.0.1:8080"));
+    #[cfg(all(feature = "server", unix))]
+    let serve = serve.arg(
+        arg!(--socket <PATH> "Listens on a Unix socket for newline-delimited JSON instead, defaults to server.socket")
+            .conflicts_with("listen"),
+    );
+    #[cfg(feature = "server")]
+    let command = command.subcommand(serve);
+    let m = command.get_matches();
+    match m.subcommand() {
+        Some(("generate", _)) => perform_generate(),
+        Some(("edit", edit_m)) => perform_edit(edit_m).await,
+        Some(("send-event", send_event_m)) => perform_send_event(send_event_m).await,
+        Some(("state", state_m)) => match state_m.subcommand() {
+            Some(("set", set_m)) => perform_set_state(set_m).await,
+            _ => unreachable!("clap requires a state subcommand"),
+        },
+        Some(("room", room_m)) => match room_m.subcommand() {
+            Some(("create", create_m)) => perform_create_room(create_m).await,
+            Some(("invite", invite_m)) => perform_invite(invite_m).await,
+            Some(("kick", kick_m)) => perform_kick(kick_m).await,
+            Some(("leave", leave_m)) => perform_leave(leave_m).await,
+            Some(("forget", forget_m)) => perform_forget(forget_m).await,
+            _ => unreachable!("clap requires a room subcommand"),
+        },
+        Some(("invites", invites_m)) => perform_invites(invites_m).await,
+        Some(("topic", topic_m)) => perform_topic(topic_m).await,
+        Some(("rename", rename_m)) => perform_rename(rename_m).await,
+        Some(("redact", redact_m)) => perform_redact(redact_m).await,
+        Some(("react", react_m)) => perform_react(react_m).await,
+        #[cfg(feature = "server")]
+        Some(("serve", serve_m)) => perform_serve(serve_m).await,
+        _ => perform_send_message(&m).await,
     }
 }
 
-async fn get_token(config: &Config, client: &reqwest::Client) -> Result<String, ApiError> {
-    match config.token.clone() {
-        Some(found_token) => match verify_token(found_token.as_str(), config, client).await {
-            Ok(valid_token) => Ok(valid_token),
-            Err(e) => {
-                eprintln!("Failed to verify token: {}", e);
-                login(config, client).await
-            }
-        },
-        None => login(config, client).await,
+async fn get_token(client: &mut MatrixClient) -> Result<String, ApiError> {
+    if client.token().is_some() {
+        match client.verify_token().await {
+            Ok(valid_token) => return Ok(valid_token),
+            Err(e) => eprintln!("Failed to verify token: {}", e),
+        }
     }
+    client.login().await
 }
@@ -0,0 +1,567 @@
+//! `POST /alertmanager`, receiving the webhooks of Prometheus Alertmanager.
+//!
+//! Each alert group is sent to the room of its receiver. The event ID of the firing
+//! message is remembered per group, so that repeated or changed notifications of the
+//! group edit it, and once the group resolves it is edited or reacted to instead of
+//! sending a new message. The event IDs are only kept in memory for a week after the
+//! last notification, groups notified after a restart or later than that are announced
+//! with a new message.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use serde::Deserialize;
+
+use crate::api::{escape_html, ApiError, Message, SentEvent};
+use crate::config::ResolveAction;
+
+use super::{authorize, ServerError, ServerState};
+
+/// Reaction added to the firing message with `on_resolve = "react"`.
+pub const RESOLVED_REACTION: &str = "✅";
+
+/// How long the firing message of a group is remembered.
+const ALERT_EVENT_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How many firing messages are remembered at most, the oldest is forgotten first.
+const ALERT_EVENTS_CAPACITY: usize = 1024;
+
+/// Event ID of the firing message of each group by group key, bounded so that groups
+/// which never resolve do not pile up.
+#[derive(Debug, Default)]
+pub(super) struct AlertEvents {
+    events: HashMap<String, (String, Instant)>,
+}
+
+impl AlertEvents {
+    fn insert(&mut self, group_key: String, event_id: String) {
+        let now = Instant::now();
+        self.events
+            .retain(|_, (_, sent_at)| now.duration_since(*sent_at) < ALERT_EVENT_MAX_AGE);
+        if self.events.len() >= ALERT_EVENTS_CAPACITY && !self.events.contains_key(&group_key) {
+            let oldest = self
+                .events
+                .iter()
+                .min_by_key(|(_, (_, sent_at))| *sent_at)
+                .map(|(group_key, _)| group_key.clone());
+            if let Some(oldest) = oldest {
+                self.events.remove(&oldest);
+            }
+        }
+        self.events.insert(group_key, (event_id, now));
+    }
+
+    fn get(&self, group_key: &str) -> Option<String> {
+        self.events
+            .get(group_key)
+            .filter(|(_, sent_at)| sent_at.elapsed() < ALERT_EVENT_MAX_AGE)
+            .map(|(event_id, _)| event_id.clone())
+    }
+
+    fn remove(&mut self, group_key: &str) {
+        self.events.remove(group_key);
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+impl AlertStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AlertStatus::Firing => "FIRING",
+            AlertStatus::Resolved => "RESOLVED",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            AlertStatus::Firing => "#dc3545",
+            AlertStatus::Resolved => "#28a745",
+        }
+    }
+}
+
+/// The webhook payload of Alertmanager, version 4.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub receiver: String,
+    pub status: AlertStatus,
+    pub group_key: String,
+    #[serde(default)]
+    pub group_labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub common_labels: BTreeMap<String, String>,
+    pub alerts: Vec<Alert>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Alert {
+    pub status: AlertStatus,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Alert {
+    fn summary(&self) -> &str {
+        self.annotations
+            .get("summary")
+            .or_else(|| self.labels.get("alertname"))
+            .map(String::as_str)
+            .unwrap_or("Alert")
+    }
+
+    /// Labels telling the alert apart from the rest of its group.
+    fn distinct_labels<'a>(
+        &'a self,
+        common_labels: &'a BTreeMap<String, String>,
+    ) -> impl Iterator<Item = (&'a String, &'a String)> {
+        self.labels
+            .iter()
+            .filter(move |(name, value)| common_labels.get(*name) != Some(value))
+    }
+}
+
+impl Notification {
+    /// Title in the style of Alertmanager's default, e.g. `[FIRING:2] HighLatency api`.
+    fn title(&self) -> String {
+        let mut title = format!("[{}", self.status.as_str());
+        if self.status == AlertStatus::Firing {
+            write!(title, ":{}", self.count(AlertStatus::Firing)).unwrap();
+        }
+        title.push(']');
+        for value in self.group_labels.values() {
+            write!(title, " {}", value).unwrap();
+        }
+        title
+    }
+
+    fn count(&self, status: AlertStatus) -> usize {
+        self.alerts
+            .iter()
+            .filter(|alert| alert.status == status)
+            .count()
+    }
+
+    /// Renders the alerts of the group, firing ones first.
+    pub fn render(&self) -> Message {
+        let title = self.title();
+        let mut text = title.clone();
+        let mut html = format!(
+            "<b><font data-mx-color=\"{}\">{}</font></b>",
+            self.status.color(),
+            escape_html(&title)
+        );
+
+        for status in [AlertStatus::Firing, AlertStatus::Resolved] {
+            let alerts: Vec<&Alert> = self
+                .alerts
+                .iter()
+                .filter(|alert| alert.status == status)
+                .collect();
+            if alerts.is_empty() {
+                continue;
+            }
+
+            let heading = match status {
+                AlertStatus::Firing => "Firing",
+                AlertStatus::Resolved => "Resolved",
+            };
+            write!(text, "\n{}:", heading).unwrap();
+            write!(html, "<p>{}:</p><ul>", heading).unwrap();
+            for alert in alerts {
+                let labels: Vec<String> = alert
+                    .distinct_labels(&self.common_labels)
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect();
+
+                write!(text, "\n- {}", alert.summary()).unwrap();
+                write!(html, "<li>{}", escape_html(alert.summary())).unwrap();
+                if !labels.is_empty() {
+                    write!(text, " ({})", labels.join(", ")).unwrap();
+                    write!(html, " <code>{}</code>", escape_html(&labels.join(", "))).unwrap();
+                }
+                if let Some(description) = alert.annotations.get("description") {
+                    write!(text, "\n  {}", description).unwrap();
+                    write!(html, "<br/>{}", escape_html(description)).unwrap();
+                }
+                html.push_str("</li>");
+            }
+            html.push_str("</ul>");
+        }
+
+        Message::html(text, html)
+    }
+}
+
+/// Sends a firing group to the room of its receiver, or updates its message once resolved.
+pub(super) async fn alertmanager(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<SentEvent>, ServerError> {
+    authorize(&headers, &state.secret)?;
+
+    let notification: Notification = serde_json::from_slice(&body).map_err(ApiError::from)?;
+    let alertmanager_config = &state.config.server.alertmanager;
+    let room = alertmanager_config
+        .room_for(&notification.receiver)
+        .ok_or_else(|| {
+            ServerError::new(
+                StatusCode::NOT_FOUND,
+                format!("No room configured for receiver {}", notification.receiver),
+            )
+        })?;
+    let message = notification
+        .render()
+        .with_msgtype(state.config.msgtype.unwrap_or_default());
+
+    let firing_event_id = state
+        .alert_events
+        .lock()
+        .unwrap()
+        .get(&notification.group_key);
+
+    let sent_event = match (notification.status, firing_event_id) {
+        (AlertStatus::Firing, Some(event_id)) => {
+            let sent_event = state.session.edit(room, &event_id, &message).await?;
+            state
+                .alert_events
+                .lock()
+                .unwrap()
+                .insert(notification.group_key, event_id);
+            sent_event
+        }
+        (AlertStatus::Firing, None) => {
+            let sent_event = state.session.send_joining(room, &[], &message).await?;
+            state
+                .alert_events
+                .lock()
+                .unwrap()
+                .insert(notification.group_key, sent_event.event_id.clone());
+            sent_event
+        }
+        (AlertStatus::Resolved, Some(event_id)) => {
+            let sent_event = match alertmanager_config.on_resolve {
+                ResolveAction::Edit => state.session.edit(room, &event_id, &message).await?,
+                ResolveAction::React => {
+                    state
+                        .session
+                        .react(room, &event_id, RESOLVED_REACTION)
+                        .await?
+                }
+            };
+            // Only forgotten once updated, Alertmanager retries a failed notification.
+            state
+                .alert_events
+                .lock()
+                .unwrap()
+                .remove(&notification.group_key);
+            sent_event
+        }
+        (AlertStatus::Resolved, None) => state.session.send_joining(room, &[], &message).await?,
+    };
+
+    Ok(Json(sent_event))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use tower::ServiceExt;
+
+    use crate::api::mock_server::{path_matcher, ANY_TXN_ID};
+    use crate::config::{Config, ResolveAction};
+    use crate::server::tests::{body_json, mock_send, post, test_router, ROOM};
+
+    use super::{AlertEvents, Notification, ALERT_EVENTS_CAPACITY};
+
+    fn notification(status: &str) -> String {
+        format!(
+            r#"{{
+                "version": "4",
+                "receiver": "database",
+                "status": "{status}",
+                "groupKey": "{{}}:{{alertname=\"DiskFull\"}}",
+                "groupLabels": {{"alertname": "DiskFull"}},
+                "commonLabels": {{"alertname": "DiskFull", "severity": "critical"}},
+                "commonAnnotations": {{}},
+                "externalURL": "http://alertmanager:9093",
+                "alerts": [
+                    {{
+                        "status": "{status}",
+                        "labels": {{"alertname": "DiskFull", "severity": "critical", "instance": "db1"}},
+                        "annotations": {{"summary": "Disk almost full", "description": "95% used"}},
+                        "startsAt": "2023-08-01T12:00:00Z",
+                        "endsAt": "0001-01-01T00:00:00Z",
+                        "fingerprint": "1"
+                    }}
+                ]
+            }}"#
+        )
+    }
+
+    fn alertmanager_config(on_resolve: ResolveAction) -> Config {
+        let mut config = Config::default();
+        config
+            .server
+            .alertmanager
+            .receivers
+            .insert("database".to_owned(), ROOM.to_owned());
+        config.server.alertmanager.on_resolve = on_resolve;
+        config
+    }
+
+    #[test]
+    fn test_render() {
+        let notification: Notification = serde_json::from_str(&notification("firing")).unwrap();
+
+        let message = notification.render();
+
+        assert_eq!(
+            message.body,
+            "[FIRING:1] DiskFull\nFiring:\n- Disk almost full (instance=db1)\n  95% used"
+        );
+        assert_eq!(
+            message.html.as_deref(),
+            Some(
+                "<b><font data-mx-color=\"#dc3545\">[FIRING:1] DiskFull</font></b>\
+                 <p>Firing:</p><ul><li>Disk almost full <code>instance=db1</code><br/>95% used</li></ul>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_alert_events_forget_oldest() {
+        let mut alert_events = AlertEvents::default();
+
+        for group in 0..=ALERT_EVENTS_CAPACITY {
+            alert_events.insert(group.to_string(), format!("$event{}:testmatrix", group));
+        }
+
+        assert_eq!(alert_events.get("0"), None);
+        assert_eq!(
+            alert_events
+                .get(&ALERT_EVENTS_CAPACITY.to_string())
+                .as_deref(),
+            Some(format!("$event{}:testmatrix", ALERT_EVENTS_CAPACITY).as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_alertmanager_resolve_edits() {
+        let mut mock_server = mockito::Server::new();
+        let firing_endpoint = mock_send(
+            &mut mock_server,
+            ROOM,
+            serde_json::json!({ "body": "[FIRING:1] DiskFull\nFiring:\n- Disk almost full (instance=db1)\n  95% used" }),
+        );
+        let router = test_router(&mock_server, alertmanager_config(ResolveAction::Edit));
+
+        let response = router
+            .clone()
+            .oneshot(post(
+                "/alertmanager",
+                "application/json",
+                notification("firing"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        firing_endpoint.assert();
+
+        let edit_endpoint = mock_send(
+            &mut mock_server,
+            ROOM,
+            serde_json::json!({
+                "m.new_content": { "body": "[RESOLVED] DiskFull\nResolved:\n- Disk almost full (instance=db1)\n  95% used" },
+                "m.relates_to": { "rel_type": "m.replace", "event_id": "$testevent:testmatrix" },
+            }),
+        );
+        let response = router
+            .oneshot(post(
+                "/alertmanager",
+                "application/json",
+                notification("resolved"),
+            ))
+            .await
+            .unwrap();
+
+        edit_endpoint.assert();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await["event_id"],
+            "$testevent:testmatrix"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_alertmanager_repeated_firing_edits() {
+        let mut mock_server = mockito::Server::new();
+        let firing_endpoint = mock_send(&mut mock_server, ROOM, serde_json::json!({}));
+        let router = test_router(&mock_server, alertmanager_config(ResolveAction::React));
+
+        router
+            .clone()
+            .oneshot(post(
+                "/alertmanager",
+                "application/json",
+                notification("firing"),
+            ))
+            .await
+            .unwrap();
+        firing_endpoint.assert();
+
+        let edit_endpoint = mock_send(
+            &mut mock_server,
+            ROOM,
+            serde_json::json!({
+                "m.new_content": { "body": "[FIRING:1] DiskFull\nFiring:\n- Disk almost full (instance=db1)\n  95% used" },
+                "m.relates_to": { "rel_type": "m.replace", "event_id": "$testevent:testmatrix" },
+            }),
+        );
+        let response = router
+            .oneshot(post(
+                "/alertmanager",
+                "application/json",
+                notification("firing"),
+            ))
+            .await
+            .unwrap();
+
+        edit_endpoint.assert();
+        firing_endpoint.assert();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_alertmanager_resolve_retries_failed_edit() {
+        let mut mock_server = mockito::Server::new();
+        let base_url = format!("http://{}", mock_server.host_with_port());
+        let firing_endpoint = mock_send(&mut mock_server, ROOM, serde_json::json!({}));
+        let router = test_router(&mock_server, alertmanager_config(ResolveAction::Edit));
+
+        router
+            .clone()
+            .oneshot(post(
+                "/alertmanager",
+                "application/json",
+                notification("firing"),
+            ))
+            .await
+            .unwrap();
+        firing_endpoint.assert();
+
+        let send_message_url = crate::config::build_send_message_url(&base_url, ROOM, ANY_TXN_ID);
+        let failed_edit_endpoint = mock_server
+            .mock("PUT", path_matcher(&base_url, &send_message_url))
+            .with_status(400)
+            .with_body(r#"{"errcode": "M_BAD_JSON", "error": "Bad edit"}"#)
+            .create();
+        let response = router
+            .clone()
+            .oneshot(post(
+                "/alertmanager",
+                "application/json",
+                notification("resolved"),
+            ))
+            .await
+            .unwrap();
+        failed_edit_endpoint.assert();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        failed_edit_endpoint.remove();
+
+        let edit_endpoint = mock_send(
+            &mut mock_server,
+            ROOM,
+            serde_json::json!({
+                "m.relates_to": { "rel_type": "m.replace", "event_id": "$testevent:testmatrix" },
+            }),
+        );
+        let response = router
+            .oneshot(post(
+                "/alertmanager",
+                "application/json",
+                notification("resolved"),
+            ))
+            .await
+            .unwrap();
+
+        edit_endpoint.assert();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_alertmanager_resolve_reacts() {
+        let mut mock_server = mockito::Server::new();
+        let base_url = format!("http://{}", mock_server.host_with_port());
+        let firing_endpoint = mock_send(&mut mock_server, ROOM, serde_json::json!({}));
+        let router = test_router(&mock_server, alertmanager_config(ResolveAction::React));
+
+        router
+            .clone()
+            .oneshot(post(
+                "/alertmanager",
+                "application/json",
+                notification("firing"),
+            ))
+            .await
+            .unwrap();
+        firing_endpoint.assert();
+
+        let reaction_url =
+            crate::config::build_send_event_url(&base_url, ROOM, "m.reaction", ANY_TXN_ID);
+        let reaction_endpoint = mock_server
+            .mock("PUT", path_matcher(&base_url, &reaction_url))
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "m.relates_to": {
+                    "rel_type": "m.annotation",
+                    "event_id": "$testevent:testmatrix",
+                    "key": "✅",
+                },
+            })))
+            .with_status(200)
+            .with_body(r#"{"event_id": "$testreaction:testmatrix"}"#)
+            .create();
+        let response = router
+            .oneshot(post(
+                "/alertmanager",
+                "application/json",
+                notification("resolved"),
+            ))
+            .await
+            .unwrap();
+
+        reaction_endpoint.assert();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_alertmanager_unknown_receiver() {
+        let mock_server = mockito::Server::new();
+
+        let response = test_router(&mock_server, Config::default())
+            .oneshot(post(
+                "/alertmanager",
+                "application/json",
+                notification("firing"),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
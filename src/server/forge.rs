@@ -0,0 +1,819 @@
+//! `POST /github`, `/gitea` and `/gitlab`, summarizing the webhooks of code forges.
+//!
+//! Pushes, pull or merge requests, issues and finished workflow runs or pipelines are
+//! sent to the room of their repository, other events are acknowledged and dropped.
+//! Forges cannot send a bearer token, so the shared secret is configured as the webhook
+//! secret instead: GitHub and Gitea sign the payload with it, GitLab sends it verbatim.
+
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::api::{escape_html, ApiError, Message};
+
+use super::{constant_time_eq, ServerError, ServerState};
+
+/// Commits listed for a push, further ones are only counted.
+pub const MAX_LISTED_COMMITS: usize = 5;
+
+/// A webhook summarized into a message for the room of its repository.
+#[derive(Debug)]
+pub struct ForgeEvent {
+    /// Full name of the repository, such as `owner/repo`.
+    pub repository: String,
+    pub message: Message,
+}
+
+#[derive(Deserialize, Debug)]
+struct Repository {
+    #[serde(alias = "path_with_namespace")]
+    full_name: String,
+    #[serde(alias = "web_url")]
+    html_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Commit {
+    id: String,
+    message: String,
+    url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct User {
+    login: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitLabUser {
+    username: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubPush {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    #[serde(alias = "compare_url")]
+    compare: Option<String>,
+    commits: Vec<Commit>,
+    /// Sent by Gitea, GitHub only sends the commits.
+    total_commits: Option<usize>,
+    repository: Repository,
+    sender: User,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubPullRequest {
+    action: String,
+    number: u64,
+    pull_request: GitHubIssue,
+    repository: Repository,
+    sender: User,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubIssues {
+    action: String,
+    issue: GitHubIssue,
+    repository: Repository,
+    sender: User,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubIssue {
+    number: u64,
+    title: String,
+    html_url: String,
+    #[serde(default)]
+    merged: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitHubWorkflowRun {
+    action: String,
+    workflow_run: WorkflowRun,
+    repository: Repository,
+}
+
+#[derive(Deserialize, Debug)]
+struct WorkflowRun {
+    name: String,
+    head_branch: String,
+    conclusion: Option<String>,
+    html_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitLabPush {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    user_username: String,
+    commits: Vec<Commit>,
+    total_commits_count: usize,
+    project: Repository,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitLabChange {
+    user: GitLabUser,
+    object_attributes: GitLabChangeAttributes,
+    project: Repository,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitLabChangeAttributes {
+    iid: u64,
+    title: String,
+    url: String,
+    action: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitLabPipeline {
+    object_attributes: GitLabPipelineAttributes,
+    project: Repository,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitLabPipelineAttributes {
+    id: u64,
+    #[serde(rename = "ref")]
+    git_ref: String,
+    status: String,
+}
+
+/// Summarizes a GitHub or Gitea webhook of the given `X-GitHub-Event` or `X-Gitea-Event`,
+/// `None` for events not worth a message.
+pub fn parse_github(event: &str, body: &[u8]) -> Result<Option<ForgeEvent>, ApiError> {
+    Ok(match event {
+        "push" => {
+            let push: GitHubPush = serde_json::from_slice(body)?;
+            let total_commits = push.total_commits.unwrap_or(push.commits.len());
+            render_push(
+                &push.repository,
+                &push.sender.login,
+                &push.git_ref,
+                &push.commits,
+                total_commits,
+                push.compare.as_deref(),
+            )
+        }
+        "pull_request" => {
+            let pull_request: GitHubPullRequest = serde_json::from_slice(body)?;
+            let action = match pull_request.action.as_str() {
+                "closed" if pull_request.pull_request.merged => "merged",
+                action => action,
+            };
+            render_change(
+                &pull_request.repository,
+                &pull_request.sender.login,
+                action,
+                "pull request",
+                &format!("#{}", pull_request.number),
+                &pull_request.pull_request.title,
+                &pull_request.pull_request.html_url,
+            )
+        }
+        "issues" => {
+            let issues: GitHubIssues = serde_json::from_slice(body)?;
+            render_change(
+                &issues.repository,
+                &issues.sender.login,
+                &issues.action,
+                "issue",
+                &format!("#{}", issues.issue.number),
+                &issues.issue.title,
+                &issues.issue.html_url,
+            )
+        }
+        "workflow_run" => {
+            let workflow_run: GitHubWorkflowRun = serde_json::from_slice(body)?;
+            let run = &workflow_run.workflow_run;
+            match (workflow_run.action.as_str(), &run.conclusion) {
+                ("completed", Some(conclusion)) => Some(render_run(
+                    &workflow_run.repository,
+                    &format!("Workflow {}", run.name),
+                    &run.html_url,
+                    conclusion,
+                    &run.head_branch,
+                )),
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Summarizes a GitLab webhook of the given `X-Gitlab-Event`,
+/// `None` for events not worth a message.
+pub fn parse_gitlab(event: &str, body: &[u8]) -> Result<Option<ForgeEvent>, ApiError> {
+    Ok(match event {
+        "Push Hook" => {
+            let push: GitLabPush = serde_json::from_slice(body)?;
+            render_push(
+                &push.project,
+                &push.user_username,
+                &push.git_ref,
+                &push.commits,
+                push.total_commits_count,
+                None,
+            )
+        }
+        "Merge Request Hook" | "Issue Hook" => {
+            let change: GitLabChange = serde_json::from_slice(body)?;
+            let attributes = &change.object_attributes;
+            let action = match attributes.action.as_deref() {
+                Some("open") => "opened",
+                Some("reopen") => "reopened",
+                Some("close") => "closed",
+                Some("merge") => "merged",
+                _ => return Ok(None),
+            };
+            let (kind, reference) = if event == "Issue Hook" {
+                ("issue", format!("#{}", attributes.iid))
+            } else {
+                ("merge request", format!("!{}", attributes.iid))
+            };
+            render_change(
+                &change.project,
+                &change.user.username,
+                action,
+                kind,
+                &reference,
+                &attributes.title,
+                &attributes.url,
+            )
+        }
+        "Pipeline Hook" => {
+            let pipeline: GitLabPipeline = serde_json::from_slice(body)?;
+            let attributes = &pipeline.object_attributes;
+            match attributes.status.as_str() {
+                "success" | "failed" | "canceled" => Some(render_run(
+                    &pipeline.project,
+                    &format!("Pipeline #{}", attributes.id),
+                    &format!(
+                        "{}/-/pipelines/{}",
+                        pipeline.project.html_url, attributes.id
+                    ),
+                    &attributes.status,
+                    &attributes.git_ref,
+                )),
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// A message built as plain text and HTML side by side.
+#[derive(Default)]
+struct Summary {
+    text: String,
+    html: String,
+}
+
+impl Summary {
+    fn push(&mut self, text: &str) -> &mut Self {
+        self.text.push_str(text);
+        self.html.push_str(&escape_html(text));
+        self
+    }
+
+    fn link(&mut self, text: &str, url: &str) -> &mut Self {
+        self.text.push_str(text);
+        self.html.push_str(&format!(
+            r#"<a href="{}">{}</a>"#,
+            escape_html(url),
+            escape_html(text)
+        ));
+        self
+    }
+
+    fn bold(&mut self, text: &str) -> &mut Self {
+        self.text.push_str(text);
+        self.html.push_str(&format!("<b>{}</b>", escape_html(text)));
+        self
+    }
+
+    fn text(&mut self, text: &str) -> &mut Self {
+        self.text.push_str(text);
+        self
+    }
+
+    fn html(&mut self, html: &str) -> &mut Self {
+        self.html.push_str(html);
+        self
+    }
+
+    fn into_event(self, repository: &Repository) -> ForgeEvent {
+        ForgeEvent {
+            repository: repository.full_name.clone(),
+            message: Message::html(self.text, self.html),
+        }
+    }
+}
+
+fn render_push(
+    repository: &Repository,
+    pusher: &str,
+    git_ref: &str,
+    commits: &[Commit],
+    total_commits: usize,
+    compare: Option<&str>,
+) -> Option<ForgeEvent> {
+    if commits.is_empty() {
+        return None;
+    }
+    let branch = git_ref
+        .strip_prefix("refs/heads/")
+        .or_else(|| git_ref.strip_prefix("refs/tags/"))
+        .unwrap_or(git_ref);
+    let count = match total_commits {
+        1 => "1 commit".to_owned(),
+        total_commits => format!("{} commits", total_commits),
+    };
+
+    let mut summary = Summary::default();
+    summary.push(&format!("{} pushed ", pusher));
+    match compare {
+        Some(compare) => summary.link(&count, compare),
+        None => summary.push(&count),
+    };
+    summary
+        .push(" to ")
+        .bold(branch)
+        .push(" in ")
+        .link(&repository.full_name, &repository.html_url)
+        .html("<ul>");
+    for commit in commits.iter().take(MAX_LISTED_COMMITS) {
+        let short_id = commit.id.get(..7).unwrap_or(&commit.id);
+        let title = commit.message.lines().next().unwrap_or_default();
+        summary
+            .text("\n- ")
+            .html("<li>")
+            .link(short_id, &commit.url)
+            .push(&format!(" {}", title))
+            .html("</li>");
+    }
+    if total_commits > MAX_LISTED_COMMITS {
+        summary
+            .text("\n- ")
+            .html("<li>")
+            .push(&format!("and {} more", total_commits - MAX_LISTED_COMMITS))
+            .html("</li>");
+    }
+    summary.html("</ul>");
+
+    Some(summary.into_event(repository))
+}
+
+/// Summarizes a pull request, merge request or issue being opened, reopened, closed or merged.
+fn render_change(
+    repository: &Repository,
+    actor: &str,
+    action: &str,
+    kind: &str,
+    reference: &str,
+    title: &str,
+    url: &str,
+) -> Option<ForgeEvent> {
+    if !matches!(action, "opened" | "reopened" | "closed" | "merged") {
+        return None;
+    }
+
+    let mut summary = Summary::default();
+    summary
+        .push(&format!("{} {} {} ", actor, action, kind))
+        .link(&format!("{}{}", repository.full_name, reference), url)
+        .push(&format!(": {}", title));
+    Some(summary.into_event(repository))
+}
+
+/// Summarizes a finished workflow run or pipeline.
+fn render_run(
+    repository: &Repository,
+    name: &str,
+    url: &str,
+    conclusion: &str,
+    branch: &str,
+) -> ForgeEvent {
+    let outcome = match conclusion {
+        "success" => "succeeded",
+        "failure" | "failed" => "failed",
+        "cancelled" | "canceled" => "was cancelled",
+        conclusion => conclusion,
+    };
+
+    let mut summary = Summary::default();
+    summary
+        .link(name, url)
+        .push(&format!(" {} on ", outcome))
+        .bold(branch)
+        .push(" in ")
+        .link(&repository.full_name, &repository.html_url);
+    summary.into_event(repository)
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn wrong_signature() -> ServerError {
+    ServerError::new(StatusCode::UNAUTHORIZED, "Missing or wrong signature")
+}
+
+/// Checks a hex encoded HMAC-SHA256 of `body`, keyed with the shared secret.
+pub fn verify_signature(
+    signature: Option<&str>,
+    secret: &str,
+    body: &[u8],
+) -> Result<(), ServerError> {
+    let signature = signature
+        .and_then(|signature| hex::decode(signature).ok())
+        .ok_or_else(wrong_signature)?;
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.verify_slice(&signature).map_err(|_| wrong_signature())
+}
+
+/// Sends a summarized event to the room of its repository, acknowledging dropped events
+/// with `204 No Content`.
+async fn relay(state: &ServerState, event: Option<ForgeEvent>) -> Result<Response, ServerError> {
+    let Some(event) = event else {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    };
+    let room = state
+        .config
+        .server
+        .forge
+        .room_for(&event.repository)
+        .ok_or_else(|| {
+            ServerError::new(
+                StatusCode::NOT_FOUND,
+                format!("No room configured for repository {}", event.repository),
+            )
+        })?;
+    let message = event
+        .message
+        .with_msgtype(state.config.msgtype.unwrap_or_default());
+
+    let sent_event = state.session.send_joining(room, &[], &message).await?;
+    Ok(Json(sent_event).into_response())
+}
+
+pub(super) async fn github(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ServerError> {
+    let signature = header(&headers, "x-hub-signature-256")
+        .and_then(|signature| signature.strip_prefix("sha256="));
+    verify_signature(signature, &state.secret, &body)?;
+
+    let event = parse_github(
+        header(&headers, "x-github-event").unwrap_or_default(),
+        &body,
+    )?;
+    relay(&state, event).await
+}
+
+pub(super) async fn gitea(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ServerError> {
+    verify_signature(header(&headers, "x-gitea-signature"), &state.secret, &body)?;
+
+    let event = parse_github(header(&headers, "x-gitea-event").unwrap_or_default(), &body)?;
+    relay(&state, event).await
+}
+
+pub(super) async fn gitlab(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ServerError> {
+    match header(&headers, "x-gitlab-token") {
+        Some(token) if constant_time_eq(token.as_bytes(), state.secret.as_bytes()) => {}
+        _ => {
+            return Err(ServerError::new(
+                StatusCode::UNAUTHORIZED,
+                "Missing or wrong shared secret",
+            ))
+        }
+    }
+
+    let event = parse_gitlab(
+        header(&headers, "x-gitlab-event").unwrap_or_default(),
+        &body,
+    )?;
+    relay(&state, event).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    use tower::ServiceExt;
+
+    use crate::config::Config;
+    use crate::server::tests::{mock_send, test_router, ROOM, SECRET};
+
+    use super::{parse_github, parse_gitlab};
+
+    const GITHUB_PUSH: &str = r#"{
+        "ref": "refs/heads/main",
+        "compare": "https://github.com/owner/repo/compare/abc...def",
+        "commits": [
+            {
+                "id": "0123456789abcdef",
+                "message": "Fix typo\n\nIn the README.",
+                "url": "https://github.com/owner/repo/commit/0123456789abcdef"
+            }
+        ],
+        "repository": {"full_name": "owner/repo", "html_url": "https://github.com/owner/repo"},
+        "sender": {"login": "alice"}
+    }"#;
+
+    const GITEA_PUSH: &str = r#"{
+        "ref": "refs/heads/main",
+        "compare_url": "https://gitea.example/owner/repo/compare/abc...def",
+        "commits": [
+            {
+                "id": "89abcdef01234567",
+                "message": "Bump version",
+                "url": "https://gitea.example/owner/repo/commit/89abcdef01234567"
+            }
+        ],
+        "total_commits": 7,
+        "repository": {"full_name": "owner/repo", "html_url": "https://gitea.example/owner/repo"},
+        "sender": {"login": "carol", "username": "carol"}
+    }"#;
+
+    const GITHUB_WORKFLOW_RUN: &str = r#"{
+        "action": "completed",
+        "workflow_run": {
+            "name": "CI",
+            "head_branch": "main",
+            "conclusion": "failure",
+            "html_url": "https://github.com/owner/repo/actions/runs/42"
+        },
+        "repository": {"full_name": "owner/repo", "html_url": "https://github.com/owner/repo"}
+    }"#;
+
+    const GITLAB_PIPELINE: &str = r#"{
+        "object_kind": "pipeline",
+        "object_attributes": {"id": 42, "ref": "main", "status": "failed"},
+        "project": {
+            "path_with_namespace": "group/project",
+            "web_url": "https://gitlab.com/group/project"
+        }
+    }"#;
+
+    const GITLAB_MERGE_REQUEST: &str = r#"{
+        "object_kind": "merge_request",
+        "user": {"name": "Bob", "username": "bob"},
+        "project": {
+            "path_with_namespace": "group/project",
+            "web_url": "https://gitlab.com/group/project"
+        },
+        "object_attributes": {
+            "iid": 7,
+            "title": "Add caching",
+            "url": "https://gitlab.com/group/project/-/merge_requests/7",
+            "action": "merge"
+        }
+    }"#;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config
+            .server
+            .forge
+            .repositories
+            .insert("owner/repo".to_owned(), ROOM.to_owned());
+        config
+    }
+
+    fn sign(body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn github_request(event: &str, signature: &str, body: &'static str) -> Request<Body> {
+        Request::post("/github")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("X-GitHub-Event", event)
+            .header("X-Hub-Signature-256", format!("sha256={}", signature))
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn gitea_request(signature: &str, body: &'static str) -> Request<Body> {
+        Request::post("/gitea")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("X-Gitea-Event", "push")
+            .header("X-Gitea-Signature", signature)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn gitlab_request(token: &str, body: &'static str) -> Request<Body> {
+        Request::post("/gitlab")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("X-Gitlab-Event", "Merge Request Hook")
+            .header("X-Gitlab-Token", token)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_github_push() {
+        let event = parse_github("push", GITHUB_PUSH.as_bytes())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(event.repository, "owner/repo");
+        assert_eq!(
+            event.message.body,
+            "alice pushed 1 commit to main in owner/repo\n- 0123456 Fix typo"
+        );
+        assert_eq!(
+            event.message.html.as_deref(),
+            Some(
+                "alice pushed <a href=\"https://github.com/owner/repo/compare/abc...def\">1 commit</a> \
+                 to <b>main</b> in <a href=\"https://github.com/owner/repo\">owner/repo</a>\
+                 <ul><li><a href=\"https://github.com/owner/repo/commit/0123456789abcdef\">0123456</a> \
+                 Fix typo</li></ul>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_gitea_push() {
+        let event = parse_github("push", GITEA_PUSH.as_bytes())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            event.message.body,
+            "carol pushed 7 commits to main in owner/repo\n- 89abcde Bump version\n- and 2 more"
+        );
+        assert!(event.message.html.unwrap().starts_with(
+            "carol pushed <a href=\"https://gitea.example/owner/repo/compare/abc...def\">7 commits</a>"
+        ));
+    }
+
+    #[test]
+    fn test_parse_github_workflow_run() {
+        let event = parse_github("workflow_run", GITHUB_WORKFLOW_RUN.as_bytes())
+            .unwrap()
+            .unwrap();
+        let in_progress = GITHUB_WORKFLOW_RUN.replace("completed", "in_progress");
+
+        assert_eq!(event.repository, "owner/repo");
+        assert_eq!(
+            event.message.body,
+            "Workflow CI failed on main in owner/repo"
+        );
+        assert!(parse_github("workflow_run", in_progress.as_bytes())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_gitlab_pipeline() {
+        let event = parse_gitlab("Pipeline Hook", GITLAB_PIPELINE.as_bytes())
+            .unwrap()
+            .unwrap();
+        let running = GITLAB_PIPELINE.replace("failed", "running");
+
+        assert_eq!(event.repository, "group/project");
+        assert_eq!(
+            event.message.body,
+            "Pipeline #42 failed on main in group/project"
+        );
+        assert!(event
+            .message
+            .html
+            .unwrap()
+            .starts_with("<a href=\"https://gitlab.com/group/project/-/pipelines/42\">"));
+        assert!(parse_gitlab("Pipeline Hook", running.as_bytes())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_gitlab_merge_request() {
+        let event = parse_gitlab("Merge Request Hook", GITLAB_MERGE_REQUEST.as_bytes())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(event.repository, "group/project");
+        assert_eq!(
+            event.message.body,
+            "bob merged merge request group/project!7: Add caching"
+        );
+    }
+
+    #[test]
+    fn test_parse_ignores_other_events() {
+        assert!(parse_github("ping", b"{}").unwrap().is_none());
+        assert!(parse_gitlab("Note Hook", b"{}").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_github_signed_push() {
+        let mut mock_server = mockito::Server::new();
+        let mock_endpoint = mock_send(
+            &mut mock_server,
+            ROOM,
+            serde_json::json!({ "body": "alice pushed 1 commit to main in owner/repo\n- 0123456 Fix typo" }),
+        );
+
+        let response = test_router(&mock_server, config())
+            .oneshot(github_request("push", &sign(GITHUB_PUSH), GITHUB_PUSH))
+            .await
+            .unwrap();
+
+        mock_endpoint.assert();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_github_rejects_wrong_signature() {
+        let mock_server = mockito::Server::new();
+
+        let response = test_router(&mock_server, config())
+            .oneshot(github_request("push", &sign("{}"), GITHUB_PUSH))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_gitea_signed_push() {
+        let mut mock_server = mockito::Server::new();
+        let mock_endpoint = mock_send(
+            &mut mock_server,
+            ROOM,
+            serde_json::json!({ "body": "carol pushed 7 commits to main in owner/repo\n- 89abcde Bump version\n- and 2 more" }),
+        );
+
+        let response = test_router(&mock_server, config())
+            .oneshot(gitea_request(&sign(GITEA_PUSH), GITEA_PUSH))
+            .await
+            .unwrap();
+
+        mock_endpoint.assert();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_gitea_rejects_wrong_signature() {
+        let mock_server = mockito::Server::new();
+
+        let response = test_router(&mock_server, config())
+            .oneshot(gitea_request(&sign("{}"), GITEA_PUSH))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_gitlab_rejects_wrong_token() {
+        let mock_server = mockito::Server::new();
+
+        let response = test_router(&mock_server, config())
+            .oneshot(gitlab_request("wrongsecret", GITLAB_MERGE_REQUEST))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_gitlab_unconfigured_repository() {
+        let mock_server = mockito::Server::new();
+
+        let response = test_router(&mock_server, config())
+            .oneshot(gitlab_request(SECRET, GITLAB_MERGE_REQUEST))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
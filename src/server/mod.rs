@@ -0,0 +1,448 @@
+//! `matrix-notify serve`, relaying incoming webhooks to Matrix over one long lived session.
+//!
+//! Enabled by the `server` feature. Every request must present the shared secret
+//! of the `[server]` config as `Authorization: Bearer <secret>`, except for forge
+//! webhooks which prove knowledge of it as described in [`forge`].
+
+pub mod alertmanager;
+pub mod forge;
+#[cfg(unix)]
+pub mod socket;
+pub mod webhook;
+
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::api::{ApiError, Message, MessageType, SentEvent};
+use crate::config::Config;
+use crate::MatrixClient;
+
+/// Address `serve` listens on when neither `--listen` nor `server.listen` is set.
+pub const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8080";
+
+/// The long lived client of the server, logging in again with the configured password
+/// when the homeserver invalidates its access token.
+pub struct Session {
+    client: RwLock<MatrixClient>,
+}
+
+impl Session {
+    pub fn new(client: MatrixClient) -> Self {
+        Self {
+            client: RwLock::new(client),
+        }
+    }
+
+    pub async fn send_joining(
+        &self,
+        room: &str,
+        via: &[String],
+        message: &Message,
+    ) -> Result<SentEvent, ApiError> {
+        let sent_event = self
+            .client
+            .read()
+            .await
+            .send_joining(room, via, message)
+            .await;
+        if !self.relogin_after(&sent_event).await {
+            return sent_event;
+        }
+        self.client
+            .read()
+            .await
+            .send_joining(room, via, message)
+            .await
+    }
+
+    pub async fn edit(
+        &self,
+        room: &str,
+        event_id: &str,
+        message: &Message,
+    ) -> Result<SentEvent, ApiError> {
+        let sent_event = self.client.read().await.edit(room, event_id, message).await;
+        if !self.relogin_after(&sent_event).await {
+            return sent_event;
+        }
+        self.client.read().await.edit(room, event_id, message).await
+    }
+
+    pub async fn react(
+        &self,
+        room: &str,
+        event_id: &str,
+        key: &str,
+    ) -> Result<SentEvent, ApiError> {
+        let sent_event = self.client.read().await.react(room, event_id, key).await;
+        if !self.relogin_after(&sent_event).await {
+            return sent_event;
+        }
+        self.client.read().await.react(room, event_id, key).await
+    }
+
+    /// Logs in again if `result` failed on an invalidated access token, telling whether
+    /// the request is worth repeating.
+    async fn relogin_after<T>(&self, result: &Result<T, ApiError>) -> bool {
+        if !matches!(result, Err(ApiError::UnknownToken { .. })) {
+            return false;
+        }
+        match self.client.write().await.login().await {
+            Ok(_) => {
+                eprintln!("Logged in again after the access token was invalidated");
+                true
+            }
+            Err(e) => {
+                eprintln!("Failed to log in again: {}", e);
+                false
+            }
+        }
+    }
+}
+
+/// Shared by all request handlers.
+pub struct ServerState {
+    pub session: Session,
+    pub config: Config,
+    secret: String,
+    alert_events: Mutex<alertmanager::AlertEvents>,
+}
+
+/// Builds the routes of the webhook server.
+pub fn router(client: MatrixClient, config: Config, secret: impl Into<String>) -> Router {
+    let state = Arc::new(ServerState {
+        session: Session::new(client),
+        config,
+        secret: secret.into(),
+        alert_events: Mutex::default(),
+    });
+
+    Router::new()
+        .route("/send/:room", post(send))
+        .route("/alertmanager", post(alertmanager::alertmanager))
+        .route("/github", post(forge::github))
+        .route("/gitea", post(forge::gitea))
+        .route("/gitlab", post(forge::gitlab))
+        .route("/webhook/:name", post(webhook::webhook))
+        .with_state(state)
+}
+
+/// Serves `router` on `listen` until interrupted with Ctrl-C.
+pub async fn serve(listen: &str, router: Router) -> Result<(), ApiError> {
+    let address: SocketAddr = listen
+        .parse()
+        .map_err(|_| ApiError::InvalidListenAddress(listen.to_owned()))?;
+    let listener = TcpListener::bind(address).map_err(|source| ApiError::Listen {
+        address: listen.to_owned(),
+        source,
+    })?;
+
+    axum::Server::from_tcp(listener)
+        .map_err(|e| ApiError::Server(e.to_string()))?
+        .serve(router.into_make_service())
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await
+        .map_err(|e| ApiError::Server(e.to_string()))
+}
+
+/// An error answered to the webhook sender as `{"error": "..."}`.
+#[derive(Debug)]
+pub struct ServerError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ServerError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<ApiError> for ServerError {
+    fn from(e: ApiError) -> Self {
+        let status = match e {
+            ApiError::SerdeJson(_)
+            | ApiError::MissingArgument(_)
+            | ApiError::UnknownMessageType(_)
+            | ApiError::Template(_)
+            | ApiError::UnknownTemplate(_)
+            | ApiError::UnknownRoomAlias(_) => StatusCode::BAD_REQUEST,
+            ApiError::RateLimited { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::BAD_GATEWAY,
+        };
+        Self::new(status, e.to_string())
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+/// Checks the bearer token of a request against the shared secret.
+pub fn authorize(headers: &HeaderMap, secret: &str) -> Result<(), ServerError> {
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match presented {
+        Some(presented) if constant_time_eq(presented.as_bytes(), secret.as_bytes()) => Ok(()),
+        _ => Err(ServerError::new(
+            StatusCode::UNAUTHORIZED,
+            "Missing or wrong shared secret",
+        )),
+    }
+}
+
+/// Compares secrets without revealing the length of the matching prefix through timing.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"))
+}
+
+#[derive(Deserialize, Debug)]
+struct SendRequestBody {
+    message: String,
+    html: Option<String>,
+    msgtype: Option<MessageType>,
+}
+
+impl SendRequestBody {
+    fn into_message(self, default_msgtype: Option<MessageType>) -> Message {
+        let message = match self.html {
+            Some(html) => Message::html(self.message, html),
+            None => Message::text(self.message),
+        };
+        message.with_msgtype(self.msgtype.or(default_msgtype).unwrap_or_default())
+    }
+}
+
+/// Relays a plain text body, or a JSON body with `message`, `html` and `msgtype`, to `room`.
+async fn send(
+    State(state): State<Arc<ServerState>>,
+    Path(room): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<SentEvent>, ServerError> {
+    authorize(&headers, &state.secret)?;
+
+    let message = if is_json(&headers) {
+        let send_request: SendRequestBody =
+            serde_json::from_slice(&body).map_err(ApiError::from)?;
+        send_request.into_message(state.config.msgtype)
+    } else {
+        let text = String::from_utf8(body.to_vec())
+            .map_err(|_| ServerError::new(StatusCode::BAD_REQUEST, "Body must be UTF-8 text"))?;
+        Message::text(text).with_msgtype(state.config.msgtype.unwrap_or_default())
+    };
+
+    Ok(Json(
+        state.session.send_joining(&room, &[], &message).await?,
+    ))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use fake::{
+        faker::internet::en::{Password, Username},
+        Fake,
+    };
+    use mockito::ServerGuard;
+    use tower::ServiceExt;
+
+    use crate::api::mock_server::{path_matcher, ANY_TXN_ID};
+    use crate::config::Config;
+    use crate::MatrixClient;
+
+    use super::{constant_time_eq, router};
+
+    pub const SECRET: &str = "testsecret";
+    pub const ROOM: &str = "!alerts:testmatrix";
+
+    /// A client of a fresh mock homeserver.
+    pub fn test_client(mock_server: &ServerGuard) -> MatrixClient {
+        let base_url = format!("http://{}", mock_server.host_with_port());
+        MatrixClient::builder()
+            .base_url(base_url.as_str())
+            .user(Username().fake::<String>(), Username().fake::<String>())
+            .token(Password(16..24).fake::<String>())
+            .build()
+            .unwrap()
+    }
+
+    /// A router relaying to a fresh mock homeserver.
+    pub fn test_router(mock_server: &ServerGuard, config: Config) -> Router {
+        router(test_client(mock_server), config, SECRET)
+    }
+
+    /// Mocks sending any message to `room`, expecting the given body.
+    pub fn mock_send(
+        mock_server: &mut ServerGuard,
+        room: &str,
+        body: serde_json::Value,
+    ) -> mockito::Mock {
+        let base_url = format!("http://{}", mock_server.host_with_port());
+        let send_message_url = crate::config::build_send_message_url(&base_url, room, ANY_TXN_ID);
+        mock_server
+            .mock("PUT", path_matcher(&base_url, &send_message_url))
+            .match_body(mockito::Matcher::PartialJson(body))
+            .with_status(200)
+            .with_body(r#"{"event_id": "$testevent:testmatrix"}"#)
+            .create()
+    }
+
+    pub fn post(uri: &str, content_type: &str, body: impl Into<Body>) -> Request<Body> {
+        Request::post(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", SECRET))
+            .header(header::CONTENT_TYPE, content_type)
+            .body(body.into())
+            .unwrap()
+    }
+
+    pub async fn body_json(response: axum::response::Response) -> serde_json::Value {
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_send_text() {
+        let mut mock_server = mockito::Server::new();
+        let mock_endpoint = mock_send(
+            &mut mock_server,
+            ROOM,
+            serde_json::json!({ "msgtype": "m.text", "body": "Disk full" }),
+        );
+
+        let response = test_router(&mock_server, Config::default())
+            .oneshot(post(&format!("/send/{}", ROOM), "text/plain", "Disk full"))
+            .await
+            .unwrap();
+
+        mock_endpoint.assert();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await["event_id"],
+            "$testevent:testmatrix"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_json() {
+        let mut mock_server = mockito::Server::new();
+        let mock_endpoint = mock_send(
+            &mut mock_server,
+            ROOM,
+            serde_json::json!({
+                "msgtype": "m.notice",
+                "body": "Disk full",
+                "formatted_body": "<b>Disk</b> full",
+            }),
+        );
+
+        let response = test_router(&mock_server, Config::default())
+            .oneshot(post(
+                &format!("/send/{}", ROOM),
+                "application/json",
+                r#"{"message": "Disk full", "html": "<b>Disk</b> full", "msgtype": "notice"}"#,
+            ))
+            .await
+            .unwrap();
+
+        mock_endpoint.assert();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_send_rejects_wrong_secret() {
+        let mock_server = mockito::Server::new();
+
+        let request = axum::http::Request::post(format!("/send/{}", ROOM))
+            .header(header::AUTHORIZATION, "Bearer wrongsecret")
+            .body(Body::from("Disk full"))
+            .unwrap();
+        let response = test_router(&mock_server, Config::default())
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_send_logs_in_again_on_unknown_token() {
+        let mut mock_server = mockito::Server::new();
+        let base_url = format!("http://{}", mock_server.host_with_port());
+        let send_message_url = crate::config::build_send_message_url(&base_url, ROOM, ANY_TXN_ID);
+        let login_url = crate::config::build_login_url(&base_url);
+
+        let unknown_token_endpoint = mock_server
+            .mock("PUT", path_matcher(&base_url, &send_message_url))
+            .with_status(401)
+            .with_body(r#"{"errcode": "M_UNKNOWN_TOKEN", "error": "Invalid access token"}"#)
+            .create();
+        let login_endpoint = mock_server
+            .mock("POST", path_matcher(&base_url, &login_url))
+            .with_status(200)
+            .with_body(
+                r#"{"user_id": "@bot:testmatrix", "access_token": "newtoken",
+                    "home_server": "testmatrix", "device_id": "TESTDEVICE"}"#,
+            )
+            .create();
+        let send_message_endpoint = mock_server
+            .mock("PUT", path_matcher(&base_url, &send_message_url))
+            .match_header("authorization", "Bearer newtoken")
+            .with_status(200)
+            .with_body(r#"{"event_id": "$testevent:testmatrix"}"#)
+            .create();
+        let client = MatrixClient::builder()
+            .base_url(base_url.as_str())
+            .user(Username().fake::<String>(), Username().fake::<String>())
+            .password(Password(16..24).fake::<String>())
+            .token(Password(16..24).fake::<String>())
+            .build()
+            .unwrap();
+
+        let response = router(client, Config::default(), SECRET)
+            .oneshot(post(&format!("/send/{}", ROOM), "text/plain", "Disk full"))
+            .await
+            .unwrap();
+
+        unknown_token_endpoint.assert();
+        login_endpoint.assert();
+        send_message_endpoint.assert();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+}
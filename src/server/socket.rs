@@ -0,0 +1,247 @@
+//! `matrix-notify serve --socket`, relaying newline-delimited JSON from local processes.
+//!
+//! Each line is a send request like `{"room": "!id:example.org", "message": "Hi"}`, with
+//! the optional `html`, `msgtype` and `via` of the other send paths. It is answered with a
+//! line holding the sent event, or `{"error": "..."}`. There is no shared secret, access
+//! is controlled by the permissions of the socket file and its directory instead.
+
+use std::fs::{self, DirBuilder, Permissions};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::net::UnixStream as StdUnixStream;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::api::{ApiError, SentEvent};
+use crate::config::Config;
+use crate::MatrixClient;
+
+use super::{SendRequestBody, Session};
+
+/// Permissions of the socket when the config does not set `server.socket_mode`.
+pub const DEFAULT_SOCKET_MODE: u32 = 0o660;
+
+#[derive(Deserialize, Debug)]
+struct SocketRequest {
+    room: String,
+    #[serde(default)]
+    via: Vec<String>,
+    #[serde(flatten)]
+    body: SendRequestBody,
+}
+
+/// Shared by all connections.
+struct Relay {
+    session: Session,
+    config: Config,
+}
+
+impl Relay {
+    async fn send(&self, line: &str) -> Result<SentEvent, ApiError> {
+        let request: SocketRequest = serde_json::from_str(line)?;
+        let message = request.body.into_message(self.config.msgtype);
+        self.session
+            .send_joining(&request.room, &request.via, &message)
+            .await
+    }
+}
+
+/// Binds a socket at `path` with the permissions `mode`, replacing a stale socket left
+/// behind by an earlier run but not one another instance is still listening on.
+///
+/// The socket is bound in a private directory next to `path` and only moved into place
+/// once its permissions are set, so it is never reachable with looser ones.
+pub fn bind(path: &Path, mode: u32) -> Result<UnixListener, ApiError> {
+    let listen_error = |source| ApiError::Listen {
+        address: path.display().to_string(),
+        source,
+    };
+
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(listen_error(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "a file that is not a socket is in the way",
+            )));
+        }
+        match StdUnixStream::connect(path) {
+            Ok(_) => {
+                return Err(listen_error(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "another instance is listening on the socket",
+                )))
+            }
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                fs::remove_file(path).map_err(listen_error)?
+            }
+            Err(e) => return Err(listen_error(e)),
+        }
+    }
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| listen_error(io::ErrorKind::InvalidInput.into()))?;
+    let private_dir = path.with_file_name(format!(
+        ".{}.{}",
+        file_name.to_string_lossy(),
+        process::id()
+    ));
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .map_err(listen_error)?;
+    let private_path = private_dir.join(file_name);
+    let bound = UnixListener::bind(&private_path).and_then(|listener| {
+        fs::set_permissions(&private_path, Permissions::from_mode(mode))?;
+        fs::rename(&private_path, path)?;
+        Ok(listener)
+    });
+    fs::remove_file(&private_path).ok();
+    fs::remove_dir(&private_dir).ok();
+    bound.map_err(listen_error)
+}
+
+/// Serves the socket bound at `path` until interrupted with Ctrl-C, then removes it.
+pub async fn serve(
+    listener: UnixListener,
+    path: PathBuf,
+    client: MatrixClient,
+    config: Config,
+) -> Result<(), ApiError> {
+    let relay = Arc::new(Relay {
+        session: Session::new(client),
+        config,
+    });
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+
+    let result = loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => break Err(ApiError::Server(e.to_string())),
+                };
+                let relay = relay.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle(stream, &relay).await {
+                        eprintln!("Socket connection failed: {}", e);
+                    }
+                });
+            }
+            _ = &mut shutdown => break Ok(()),
+        }
+    };
+
+    fs::remove_file(&path).ok();
+    result
+}
+
+/// Answers every line of a connection until the peer closes it.
+async fn handle(stream: UnixStream, relay: &Relay) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match relay.send(&line).await {
+            Ok(sent_event) => json!(sent_event),
+            Err(e) => json!({ "error": e.to_string() }),
+        };
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    use serde_json::{json, Value};
+    use tempfile::tempdir;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixStream;
+
+    use crate::config::Config;
+    use crate::server::tests::{mock_send, test_client, ROOM};
+
+    use super::{bind, serve, DEFAULT_SOCKET_MODE};
+
+    #[tokio::test]
+    async fn test_socket_send() {
+        let mut mock_server = mockito::Server::new();
+        let mock_endpoint = mock_send(
+            &mut mock_server,
+            ROOM,
+            json!({ "msgtype": "m.notice", "body": "Backup finished" }),
+        );
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let path = temp_dir.path().join("matrix-notify.sock");
+
+        let listener = bind(&path, 0o600).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        tokio::spawn(serve(
+            listener,
+            path.clone(),
+            test_client(&mock_server),
+            Config::default(),
+        ));
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        writer
+            .write_all(
+                format!(
+                    "{}\nnot json\n",
+                    json!({ "room": ROOM, "message": "Backup finished", "msgtype": "notice" })
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut lines = BufReader::new(reader).lines();
+        let sent: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let failed: Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+
+        mock_endpoint.assert();
+        assert_eq!(sent["event_id"], "$testevent:testmatrix");
+        assert!(failed["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_bind_replaces_stale_socket() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let path = temp_dir.path().join("matrix-notify.sock");
+
+        drop(bind(&path, DEFAULT_SOCKET_MODE).unwrap());
+
+        assert!(bind(&path, DEFAULT_SOCKET_MODE).is_ok());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_bind_keeps_live_socket() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let path = temp_dir.path().join("matrix-notify.sock");
+
+        let _listener = bind(&path, DEFAULT_SOCKET_MODE).unwrap();
+
+        assert!(bind(&path, DEFAULT_SOCKET_MODE).is_err());
+        assert!(UnixStream::connect(&path).await.is_ok());
+    }
+}
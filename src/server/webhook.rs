@@ -0,0 +1,189 @@
+//! `POST /webhook/{name}`, mapping arbitrary JSON to messages with routes from the config.
+//!
+//! The fields of a JSON object payload are the variables of the route's templates, and
+//! the whole payload is available as `payload`, so nested values can be picked with
+//! expressions like `{{ alerts[0].labels.alertname }}`.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use serde_json::{Map, Value};
+
+use crate::api::{ApiError, Message, SentEvent};
+use crate::config::WebhookRoute;
+use crate::template;
+
+use super::{authorize, ServerError, ServerState};
+
+/// Template variables of a payload.
+fn context(payload: Value) -> Map<String, Value> {
+    let mut context = match &payload {
+        Value::Object(fields) => fields.clone(),
+        _ => Map::new(),
+    };
+    context.entry("payload").or_insert(payload);
+    context
+}
+
+/// Renders the target room and message of `route` for a payload.
+pub fn render(
+    route: &WebhookRoute,
+    templates: &BTreeMap<String, String>,
+    payload: Value,
+) -> Result<(String, Message), ApiError> {
+    let context = context(payload);
+
+    let room = template::render(&route.room, templates, &context)?;
+    let body = template::render(&route.template, templates, &context)?;
+    let message = match &route.html_template {
+        Some(html_template) => Message::html(
+            body,
+            template::render_html(html_template, templates, &context)?,
+        ),
+        None => Message::text(body),
+    };
+    Ok((room.trim().to_owned(), message))
+}
+
+/// Relays a JSON payload as described by the configured route `name`.
+pub(super) async fn webhook(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<SentEvent>, ServerError> {
+    authorize(&headers, &state.secret)?;
+
+    let route = state.config.server.webhooks.get(&name).ok_or_else(|| {
+        ServerError::new(StatusCode::NOT_FOUND, format!("No webhook route {}", name))
+    })?;
+    let payload: Value = serde_json::from_slice(&body).map_err(ApiError::from)?;
+    let (room, message) = render(route, &state.config.templates, payload)?;
+    let message = message.with_msgtype(route.msgtype.or(state.config.msgtype).unwrap_or_default());
+
+    Ok(Json(
+        state.session.send_joining(&room, &[], &message).await?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use crate::api::MessageType;
+    use crate::config::{Config, WebhookRoute};
+    use crate::server::tests::{mock_send, post, test_router, ROOM};
+
+    use super::render;
+
+    const GRAFANA_PAYLOAD: &str = r#"{
+        "title": "[Alerting] High CPU",
+        "state": "alerting",
+        "message": "CPU above 90%",
+        "evalMatches": [{"metric": "web1", "value": 97}, {"metric": "web2", "value": 93}],
+        "tags": {"room": "!ops:testmatrix"}
+    }"#;
+
+    fn grafana_route() -> WebhookRoute {
+        WebhookRoute {
+            room: "{{ tags.room }}".to_owned(),
+            template: "{{ title }}: {{ evalMatches | map(attribute=\"metric\") | join(\", \") }}"
+                .to_owned(),
+            html_template: Some("<b>{{ title }}</b> {{ message }}".to_owned()),
+            msgtype: None,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let payload = serde_json::from_str(GRAFANA_PAYLOAD).unwrap();
+
+        let (room, message) = render(&grafana_route(), &BTreeMap::new(), payload).unwrap();
+
+        assert_eq!(room, "!ops:testmatrix");
+        assert_eq!(message.body, "[Alerting] High CPU: web1, web2");
+        assert_eq!(
+            message.html.as_deref(),
+            Some("<b>[Alerting] High CPU</b> CPU above 90%")
+        );
+    }
+
+    #[test]
+    fn test_render_escapes_html() {
+        let route = WebhookRoute {
+            room: ROOM.to_owned(),
+            template: "{{ title }}".to_owned(),
+            html_template: Some("<b>{{ title }}</b>".to_owned()),
+            msgtype: None,
+        };
+
+        let (_, message) = render(&route, &BTreeMap::new(), json!({ "title": "<b>&" })).unwrap();
+
+        assert_eq!(message.body, "<b>&");
+        assert_eq!(message.html.as_deref(), Some("<b>&lt;b&gt;&amp;</b>"));
+    }
+
+    #[test]
+    fn test_render_array_payload() {
+        let route = WebhookRoute {
+            room: ROOM.to_owned(),
+            template: "{{ payload | length }} events".to_owned(),
+            ..WebhookRoute::default()
+        };
+
+        let (room, message) = render(&route, &BTreeMap::new(), json!([1, 2, 3])).unwrap();
+
+        assert_eq!(room, ROOM);
+        assert_eq!(message.body, "3 events");
+    }
+
+    #[tokio::test]
+    async fn test_webhook() {
+        let mut mock_server = mockito::Server::new();
+        let mock_endpoint = mock_send(
+            &mut mock_server,
+            "!ops:testmatrix",
+            json!({ "msgtype": "m.notice", "body": "[Alerting] High CPU: web1, web2" }),
+        );
+        let mut config = Config::default();
+        let mut route = grafana_route();
+        route.msgtype = Some(MessageType::Notice);
+        config.server.webhooks.insert("grafana".to_owned(), route);
+
+        let response = test_router(&mock_server, config)
+            .oneshot(post(
+                "/webhook/grafana",
+                "application/json",
+                GRAFANA_PAYLOAD,
+            ))
+            .await
+            .unwrap();
+
+        mock_endpoint.assert();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_unknown_route() {
+        let mock_server = mockito::Server::new();
+
+        let response = test_router(&mock_server, Config::default())
+            .oneshot(post(
+                "/webhook/grafana",
+                "application/json",
+                GRAFANA_PAYLOAD,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,106 @@
+//! Message templating, rendering Jinja-style templates into message bodies.
+//!
+//! Named templates from the config are registered alongside the rendered template,
+//! so they can be shared across the team and pulled in with `{% include "name" %}`.
+
+use std::collections::BTreeMap;
+
+use minijinja::{AutoEscape, Environment, UndefinedBehavior};
+use serde::Serialize;
+
+use crate::api::ApiError;
+
+fn environment(templates: &BTreeMap<String, String>) -> Result<Environment<'_>, ApiError> {
+    let mut environment = Environment::new();
+    environment.set_undefined_behavior(UndefinedBehavior::SemiStrict);
+    for (name, template) in templates {
+        environment.add_template(name, template)?;
+    }
+    Ok(environment)
+}
+
+/// Renders `source` with `context`, making `templates` available for includes.
+pub fn render<S: Serialize>(
+    source: &str,
+    templates: &BTreeMap<String, String>,
+    context: S,
+) -> Result<String, ApiError> {
+    Ok(environment(templates)?.render_str(source, context)?)
+}
+
+/// Like `render`, but HTML escapes every value for use in a formatted message.
+pub fn render_html<S: Serialize>(
+    source: &str,
+    templates: &BTreeMap<String, String>,
+    context: S,
+) -> Result<String, ApiError> {
+    let mut environment = environment(templates)?;
+    environment.set_auto_escape_callback(|_| AutoEscape::Html);
+    Ok(environment.render_str(source, context)?)
+}
+
+/// Renders the template stored in the config under `name`.
+pub fn render_named<S: Serialize>(
+    name: &str,
+    templates: &BTreeMap<String, String>,
+    context: S,
+) -> Result<String, ApiError> {
+    let source = templates
+        .get(name)
+        .ok_or_else(|| ApiError::UnknownTemplate(name.to_owned()))?;
+    render(source, templates, context)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use matches::assert_matches;
+    use serde_json::json;
+
+    use crate::api::ApiError;
+
+    use super::{render, render_named};
+
+    const ALERT_TEMPLATE: &str = "{% if firing %}FIRING{% else %}RESOLVED{% endif %}: \
+{% for host in hosts %}{{ host }}{% if not loop.last %}, {% endif %}{% endfor %}";
+
+    #[test]
+    fn test_render_conditionals_and_loops() {
+        let context = json!({ "firing": true, "hosts": ["db1", "db2"] });
+
+        let rendered = render(ALERT_TEMPLATE, &BTreeMap::new(), &context).unwrap();
+
+        assert_eq!(rendered, "FIRING: db1, db2");
+    }
+
+    #[test]
+    fn test_render_named_with_include() {
+        let templates = BTreeMap::from([
+            ("alert".to_owned(), ALERT_TEMPLATE.to_owned()),
+            (
+                "deploy".to_owned(),
+                "[{{ env }}] {% include \"alert\" %}".to_owned(),
+            ),
+        ]);
+        let context = json!({ "env": "prod", "firing": false, "hosts": ["web1"] });
+
+        let rendered = render_named("deploy", &templates, &context).unwrap();
+
+        assert_eq!(rendered, "[prod] RESOLVED: web1");
+    }
+
+    #[test]
+    fn test_render_fails_on_missing_variable() {
+        let result = render("Disk {{ disk }} full", &BTreeMap::new(), json!({}));
+
+        assert_matches!(result, Err(ApiError::Template(_)));
+    }
+
+    #[test]
+    fn test_render_named_fails_on_unknown_template() {
+        let result = render_named("missing", &BTreeMap::new(), json!({}));
+
+        assert_matches!(result, Err(ApiError::UnknownTemplate(_)));
+    }
+}
@@ -5,12 +5,11 @@ use std::{
     env::set_current_dir,
     fs::{self, File},
     io::Write,
+    path::PathBuf,
     process::Command,
 };
 use tempfile::{tempdir, TempDir};
 
-use matrix_notify;
-
 fn prepare() -> Result<TempDir, Box<dyn std::error::Error>> {
     let temp_dir = tempdir()?;
     set_current_dir(temp_dir.path())?;
@@ -18,6 +17,28 @@ fn prepare() -> Result<TempDir, Box<dyn std::error::Error>> {
     Ok(temp_dir)
 }
 
+fn write_token_config(
+    temp_dir: &TempDir,
+    base_url: &str,
+    full_username: &str,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let config = format!(
+        r#"
+base_url = "{}"
+local_username = "testuser"
+full_username = "{}"
+token = "testtoken"
+"#,
+        base_url, full_username
+    );
+
+    let temp_file_path = temp_dir.path().join("matrix-notify.toml");
+    let mut temp_file = File::create(&temp_file_path)?;
+    temp_file.write_all(config.as_bytes())?;
+
+    Ok(temp_file_path)
+}
+
 #[test]
 #[file_serial]
 fn test_no_args() -> Result<(), Box<dyn std::error::Error>> {
@@ -108,7 +129,9 @@ password = "{}"
     cmd.arg("--message");
     cmd.arg("Lorem ipsum dolor sit amet");
 
-    cmd.assert().success();
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("$testevent:testmatrix"));
 
     let metadata_result = fs::metadata(&temp_file_path);
     assert!(metadata_result.is_ok());
@@ -119,7 +142,7 @@ password = "{}"
     // mock_server.profile_endpoint.assert();
     mock_server.login_endpoint.assert();
 
-    mock_server.room_members_endpoint.assert();
+    assert!(!mock_server.joined_rooms_endpoint.matched());
     // mock_server.join_room_endpoint.assert();
 
     mock_server.send_message_endpoint.assert();
@@ -162,7 +185,9 @@ token = "{}"
     cmd.arg("--message");
     cmd.arg("Lorem ipsum dolor sit amet");
 
-    cmd.assert().success();
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("$testevent:testmatrix"));
 
     let metadata_result = fs::metadata(&temp_file_path);
     assert!(metadata_result.is_ok());
@@ -173,10 +198,841 @@ token = "{}"
     mock_server.profile_endpoint.assert();
     // mock_server.login_endpoint.assert();
 
-    mock_server.room_members_endpoint.assert();
+    assert!(!mock_server.joined_rooms_endpoint.matched());
     // mock_server.join_room_endpoint.assert();
 
     mock_server.send_message_endpoint.assert();
 
     Ok(())
 }
+
+#[test]
+#[file_serial]
+fn test_send_with_password_malformed_login() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let local_username = "testuser";
+    let full_username = "@testuser:testmatrix";
+    let password = "testpassword";
+    let mock_server =
+        matrix_notify::api::mock_server::MockMatrix::new_malformed(room, full_username);
+
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let config = format!(
+        r#"
+base_url = "{}"
+local_username = "{}"
+full_username = "{}"
+password = "{}"
+"#,
+        base_url, local_username, full_username, password
+    );
+
+    let temp_dir = prepare()?;
+    let temp_file_path = temp_dir.path().join("matrix-notify.toml");
+    let mut temp_file = File::create(&temp_file_path)?;
+    temp_file
+        .write_all(config.as_bytes())
+        .expect("Failed to write to temporary config file");
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.env("RUST_BACKTRACE", "1");
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--message");
+    cmd.arg("Lorem ipsum dolor sit amet");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Malformed response"))
+        .stderr(predicate::str::contains("panicked").not());
+
+    mock_server.login_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_send_with_token_malformed_send_message() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let local_username = "testuser";
+    let full_username = "@testuser:testmatrix";
+    let token = "testtoken";
+    let mock_server =
+        matrix_notify::api::mock_server::MockMatrix::new_malformed(room, full_username);
+
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let config = format!(
+        r#"
+base_url = "{}"
+local_username = "{}"
+full_username = "{}"
+token = "{}"
+"#,
+        base_url, local_username, full_username, token
+    );
+
+    let temp_dir = prepare()?;
+    let temp_file_path = temp_dir.path().join("matrix-notify.toml");
+    let mut temp_file = File::create(&temp_file_path)?;
+    temp_file
+        .write_all(config.as_bytes())
+        .expect("Failed to write to temporary config file");
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.env("RUST_BACKTRACE", "1");
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--message");
+    cmd.arg("Lorem ipsum dolor sit amet");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Malformed response"))
+        .stderr(predicate::str::contains("panicked").not());
+
+    mock_server.profile_endpoint.assert();
+    mock_server.send_message_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_missing_message() -> Result<(), Box<dyn std::error::Error>> {
+    prepare()?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("--room");
+    cmd.arg("!roomid:testmatrix.org");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("MESSAGE must be provided"))
+        .stderr(predicate::str::contains("panicked").not());
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_send_with_json_output() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--message");
+    cmd.arg("Lorem ipsum dolor sit amet");
+    cmd.arg("--output");
+    cmd.arg("json");
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let sent_event: serde_json::Value = serde_json::from_slice(&output)?;
+
+    assert_eq!(sent_event["room_id"], room);
+    assert_eq!(sent_event["event_id"], "$testevent:testmatrix");
+    assert!(sent_event["txn_id"]
+        .as_str()
+        .is_some_and(|txn_id| !txn_id.is_empty()));
+
+    mock_server.send_message_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_edit() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("edit");
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--event");
+    cmd.arg("$original:testmatrix");
+    cmd.arg("--message");
+    cmd.arg("Deploy in progress 40%");
+    cmd.arg("--html");
+    cmd.arg("<b>Deploy</b> in progress 40%");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("$testevent:testmatrix"));
+
+    mock_server.send_message_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_redact() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("redact");
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--event");
+    cmd.arg("$leaked:testmatrix");
+    cmd.arg("--reason");
+    cmd.arg("Leaked credentials");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("$testredaction:testmatrix"));
+
+    mock_server.redact_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_react() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("react");
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--event");
+    cmd.arg("$alert:testmatrix");
+    cmd.arg("--key");
+    cmd.arg("✅");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("$testreaction:testmatrix"));
+
+    mock_server.reaction_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_send_with_mentions() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--message");
+    cmd.arg("Disk full");
+    cmd.arg("--mention");
+    cmd.arg(full_username);
+    cmd.arg("--mention-room");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("$testevent:testmatrix"));
+
+    mock_server.send_message_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_send_with_msgtype() -> Result<(), Box<dyn std::error::Error>> {
+    use matrix_notify::api::mock_server::{path_matcher, ANY_TXN_ID};
+    use matrix_notify::config::{build_profile_url, build_send_message_url};
+
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mut server = mockito::Server::new();
+    let base_url = format!("http://{}", server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let profile_url = build_profile_url(&base_url, full_username);
+    let send_message_url = build_send_message_url(&base_url, room, ANY_TXN_ID);
+    let _profile_endpoint = server
+        .mock("GET", profile_url.strip_prefix(base_url.as_str()).unwrap())
+        .with_status(200)
+        .with_body("{}")
+        .create();
+    let send_message_endpoint = server
+        .mock("PUT", path_matcher(&base_url, &send_message_url))
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "msgtype": "m.notice",
+            "body": "Backup finished",
+        })))
+        .with_status(200)
+        .with_body(r#"{"event_id": "$testevent:testmatrix"}"#)
+        .create();
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--message");
+    cmd.arg("Backup finished");
+    cmd.arg("--msgtype");
+    cmd.arg("notice");
+
+    cmd.assert().success();
+
+    send_message_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_send_with_template() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+    fs::write(
+        temp_dir.path().join("alert.j2"),
+        "{{ severity }}: {% for host in hosts %}{{ host }} {% endfor %}",
+    )?;
+
+    let mut cmd = assert_cmd::Command::cargo_bin("matrix-notify")?;
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--template");
+    cmd.arg("alert.j2");
+    cmd.arg("--var");
+    cmd.arg("severity=critical");
+    cmd.arg("--stdin");
+    cmd.write_stdin(r#"{"hosts": ["db1", "db2"]}"#);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("$testevent:testmatrix"));
+
+    mock_server.send_message_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_send_with_template_missing_variable() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+    fs::write(
+        temp_dir.path().join("alert.j2"),
+        "{{ severity }}: disk full",
+    )?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--template");
+    cmd.arg("alert.j2");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to render template"))
+        .stderr(predicate::str::contains("panicked").not());
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_send_event() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+    fs::write(
+        temp_dir.path().join("deploy.json"),
+        r#"{"service": "api", "version": "1.4.2"}"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("send-event");
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--type");
+    cmd.arg(matrix_notify::api::mock_server::TEST_EVENT_TYPE);
+    cmd.arg("--content-json");
+    cmd.arg("deploy.json");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("$testcustomevent:testmatrix"));
+
+    mock_server.send_event_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_topic() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("topic");
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("Incident: database degraded");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("$testtopic:testmatrix"));
+
+    mock_server.topic_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_send_direct_message() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("--to");
+    cmd.arg("@alice:testmatrix");
+    cmd.arg("--message");
+    cmd.arg("Your backup finished");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("$testevent:testmatrix"));
+
+    mock_server.get_direct_rooms_endpoint.assert();
+    mock_server.create_room_endpoint.assert();
+    mock_server.set_direct_rooms_endpoint.assert();
+    mock_server.send_message_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_room_create() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("room");
+    cmd.arg("create");
+    cmd.arg("--name");
+    cmd.arg("Alerts");
+    cmd.arg("--preset");
+    cmd.arg("private_chat");
+    cmd.arg("--invite");
+    cmd.arg("@alice:testmatrix");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(room));
+
+    mock_server.create_room_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_room_invite_and_kick() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    for action in ["invite", "kick"] {
+        let mut cmd = Command::cargo_bin("matrix-notify")?;
+        cmd.arg("room");
+        cmd.arg(action);
+        cmd.arg("--room");
+        cmd.arg(room);
+        cmd.arg("--user");
+        cmd.arg("@alice:testmatrix");
+
+        cmd.assert().success();
+    }
+
+    mock_server.invite_endpoint.assert();
+    mock_server.kick_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_room_leave_and_forget() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    for action in ["leave", "forget"] {
+        let mut cmd = Command::cargo_bin("matrix-notify")?;
+        cmd.arg("room");
+        cmd.arg(action);
+        cmd.arg("--room");
+        cmd.arg(room);
+
+        cmd.assert().success();
+    }
+
+    mock_server.leave_room_endpoint.assert();
+    mock_server.forget_room_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_invites_accept() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("invites");
+    cmd.arg("--accept");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(room))
+        .stdout(predicate::str::contains("@admin:testmatrix"));
+
+    mock_server.invites_sync_endpoint.assert();
+    mock_server.join_room_endpoint.assert();
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_send_skips_membership_check() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--message");
+    cmd.arg("Lorem ipsum");
+
+    cmd.assert().success();
+
+    mock_server.send_message_endpoint.assert();
+    assert!(!mock_server.joined_rooms_endpoint.matched());
+    assert!(!mock_server.join_room_endpoint.matched());
+
+    Ok(())
+}
+
+/// Mocks a send refused with `M_FORBIDDEN` until the room is joined through `via`.
+fn mock_send_after_join(
+    server: &mut mockito::Server,
+    room: &str,
+    full_username: &str,
+    via: &[String],
+) -> Vec<mockito::Mock> {
+    use matrix_notify::api::mock_server::{path_matcher, ANY_TXN_ID};
+    use matrix_notify::config::{build_join_room_url, build_profile_url, build_send_message_url};
+
+    let base_url = format!("http://{}", server.host_with_port());
+    let profile_url = build_profile_url(&base_url, full_username);
+    let send_message_url = build_send_message_url(&base_url, room, ANY_TXN_ID);
+    let join_room_url = build_join_room_url(&base_url, room, via);
+
+    vec![
+        server
+            .mock("GET", profile_url.strip_prefix(base_url.as_str()).unwrap())
+            .with_status(200)
+            .with_body("{}")
+            .create(),
+        server
+            .mock("PUT", path_matcher(&base_url, &send_message_url))
+            .with_status(403)
+            .with_body(r#"{"errcode": "M_FORBIDDEN", "error": "User not in room"}"#)
+            .create(),
+        server
+            .mock(
+                "POST",
+                join_room_url.strip_prefix(base_url.as_str()).unwrap(),
+            )
+            .with_status(200)
+            .with_body("{}")
+            .create(),
+        server
+            .mock("PUT", path_matcher(&base_url, &send_message_url))
+            .with_status(200)
+            .with_body(r#"{"event_id": "$testevent:testmatrix"}"#)
+            .create(),
+    ]
+}
+
+#[test]
+#[file_serial]
+fn test_send_joins_on_forbidden() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mut server = mockito::Server::new();
+    let base_url = format!("http://{}", server.host_with_port());
+    let endpoints = mock_send_after_join(&mut server, room, full_username, &[]);
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--message");
+    cmd.arg("Lorem ipsum");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("$testevent:testmatrix"));
+
+    for endpoint in &endpoints {
+        endpoint.assert();
+    }
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_send_joins_via_servers() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mut server = mockito::Server::new();
+    let base_url = format!("http://{}", server.host_with_port());
+    let endpoints = mock_send_after_join(
+        &mut server,
+        room,
+        full_username,
+        &["relay.testmatrix.org".to_owned()],
+    );
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.arg("--room");
+    cmd.arg(room);
+    cmd.arg("--via");
+    cmd.arg("relay.testmatrix.org");
+    cmd.arg("--message");
+    cmd.arg("Lorem ipsum");
+
+    cmd.assert().success();
+
+    for endpoint in &endpoints {
+        endpoint.assert();
+    }
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_serve_requires_secret() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.env_remove("MATRIX_NOTIFY_SECRET");
+    cmd.arg("serve");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("MATRIX_NOTIFY_SECRET"));
+
+    Ok(())
+}
+
+#[test]
+#[file_serial]
+fn test_serve_rejects_empty_secret() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    let config_path = write_token_config(&temp_dir, &base_url, full_username)?;
+    let mut config_file = fs::OpenOptions::new().append(true).open(config_path)?;
+    writeln!(config_file, "[server]\nsecret = \"  \"")?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.env_remove("MATRIX_NOTIFY_SECRET");
+    cmd.arg("serve");
+    cmd.arg("--listen");
+    cmd.arg("127.0.0.1:0");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("MATRIX_NOTIFY_SECRET"));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+#[file_serial]
+fn test_serve_listen_overrides_config_socket() -> Result<(), Box<dyn std::error::Error>> {
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    let config_path = write_token_config(&temp_dir, &base_url, full_username)?;
+    let socket_path = temp_dir.path().join("matrix-notify.sock");
+    let mut config_file = fs::OpenOptions::new().append(true).open(config_path)?;
+    writeln!(config_file, "[server]\nsocket = {:?}", socket_path)?;
+
+    let mut cmd = Command::cargo_bin("matrix-notify")?;
+    cmd.env_remove("MATRIX_NOTIFY_SECRET");
+    cmd.arg("serve");
+    cmd.arg("--listen");
+    cmd.arg("127.0.0.1:0");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("MATRIX_NOTIFY_SECRET"));
+    assert!(!socket_path.exists());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+#[file_serial]
+fn test_serve_socket() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+    use std::{thread::sleep, time::Duration};
+
+    let room = "!roomid:testmatrix.org";
+    let full_username = "@testuser:testmatrix";
+    let mock_server = matrix_notify::api::mock_server::MockMatrix::new(room, full_username);
+    let base_url = format!("http://{}", mock_server.server.host_with_port());
+
+    let temp_dir = prepare()?;
+    write_token_config(&temp_dir, &base_url, full_username)?;
+    let socket_path = temp_dir.path().join("matrix-notify.sock");
+
+    let mut child = Command::cargo_bin("matrix-notify")?
+        .arg("serve")
+        .arg("--socket")
+        .arg(&socket_path)
+        .spawn()?;
+    let mut stream = None;
+    for _ in 0..100 {
+        match UnixStream::connect(&socket_path) {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            }
+            Err(_) => sleep(Duration::from_millis(50)),
+        }
+    }
+    let Some(mut stream) = stream else {
+        child.kill()?;
+        return Err("Socket was not created".into());
+    };
+
+    writeln!(
+        stream,
+        r#"{{"room": "{}", "message": "Backup finished"}}"#,
+        room
+    )?;
+    let mut response = String::new();
+    BufReader::new(&stream).read_line(&mut response)?;
+    child.kill()?;
+    child.wait()?;
+
+    assert!(response.contains("$testevent:testmatrix"));
+    mock_server.send_message_endpoint.assert();
+
+    Ok(())
+}